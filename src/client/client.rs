@@ -1,44 +1,100 @@
 use std::{io::{Read, Write}, net::SocketAddr};
 
-use tokio::{io::AsyncWriteExt, net::TcpStream};
+use tokio::{io::{AsyncRead, AsyncReadExt, AsyncWrite}, net::TcpStream};
+use tokio_rustls::rustls::{ClientConnection, StreamOwned};
+use tokio_rustls::TlsConnector;
 
+use crate::errors::client::ConnectionClosedError;
 use crate::models::request::HttpRequest;
+use crate::models::response::HttpResponse;
 
-pub async fn send_async_request(socket: SocketAddr, req: HttpRequest) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let message = format!("{}", req);
+use super::response::{ReadResult, ResponseParser};
+use super::tls::{self, ClientTls};
+
+// A connection to a server, with or without TLS
+pub trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> Stream for S {}
+
+// HTTP/1.1 requires the Host header, so we add it if the caller didn't
+pub(super) fn with_host(socket: SocketAddr, mut req: HttpRequest) -> HttpRequest {
+    if !req.headers.contains("Host") {
+        req.headers.insert("Host".to_string(), socket.to_string());
+    }
+
+    req
+}
+
+pub async fn connect(socket: SocketAddr, tls: Option<&ClientTls>) -> ReadResult<Box<dyn Stream>> {
+    let stream = TcpStream::connect(socket).await?;
+
+    let Some(tls) = tls else {
+        return Ok(Box::new(stream));
+    };
+
+    let connector = TlsConnector::from(tls.config());
+    Ok(Box::new(connector.connect(tls.server_name(socket)?, stream).await?))
+}
+
+// Reads until the response is complete, as it can take any number of reads.
+// Also returns whether the connection can take another request
+pub async fn read_response<S: AsyncRead + Unpin + ?Sized>(stream: &mut S, head_only: bool) -> ReadResult<(HttpResponse, bool)> {
+    let mut parser = ResponseParser::new(head_only);
+    let mut buf = vec![0u8; 8192];
+    let mut received = false;
 
-    let mut stream = TcpStream::connect(socket).await?;
-    let _ = stream.write_all(message.as_bytes()).await;
-    
     loop {
-        // Wait for the socket to be readable
-        let _ = stream.readable().await;
-
-        let mut buf = vec![0; 4096];
-
-        // Try to read data, this may still fail with `WouldBlock`
-        // if the readiness event is a false positive.
-        match stream.try_read(&mut buf) {
-            Ok(0) => return Ok(vec![0; 0]),
-            Ok(n) => {
-                buf.truncate(n);
-                return Ok(buf);
-            }
-            Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                continue;
-            }
-            Err(e) => {
-                return Err(Box::new(e));
-            }
+        let read = match stream.read(&mut buf).await {
+            Ok(read) => read,
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionReset && !received => 0,
+            Err(e) => return Err(Box::new(e)),
+        };
+
+        // Told apart from a partial response, as the server may not have
+        // handled the request at all
+        if read == 0 && !received {
+            return Err(Box::new(ConnectionClosedError));
+        }
+
+        received = true;
+        parser.push(&buf[..read]);
+
+        if let Some(res) = parser.parse(read == 0)? {
+            return Ok((res, parser.reusable()));
         }
     }
 }
 
-pub fn send_sync_request(socket: SocketAddr, req: HttpRequest) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    let mut stream = std::net::TcpStream::connect(socket)?;
-    stream.write_all(format!("{}", req).as_bytes())?;
+// Uses TLS if it was configured for the process
+pub fn send_sync_request(socket: SocketAddr, req: HttpRequest) -> ReadResult<HttpResponse> {
+    send_sync_request_tls(socket, req, tls::configured())
+}
+
+pub fn send_sync_request_tls(socket: SocketAddr, req: HttpRequest, tls: Option<&ClientTls>) -> ReadResult<HttpResponse> {
+    let head_only = req.method == "HEAD";
+    let message = with_host(socket, req).to_bytes();
+    let stream = std::net::TcpStream::connect(socket)?;
+
+    let Some(tls) = tls else {
+        return exchange_sync(stream, message, head_only);
+    };
+
+    let connection = ClientConnection::new(tls.config(), tls.server_name(socket)?)?;
+    exchange_sync(StreamOwned::new(connection, stream), message, head_only)
+}
+
+fn exchange_sync<S: Read + Write>(mut stream: S, message: Vec<u8>, head_only: bool) -> ReadResult<HttpResponse> {
+    stream.write_all(&message)?;
 
-    let mut buf = vec![0u8; 4096];
-    stream.read(&mut buf)?;
-    Ok(buf)
-}
\ No newline at end of file
+    let mut parser = ResponseParser::new(head_only);
+    let mut buf = vec![0u8; 8192];
+
+    loop {
+        let read = stream.read(&mut buf)?;
+        parser.push(&buf[..read]);
+
+        if let Some(res) = parser.parse(read == 0)? {
+            return Ok(res);
+        }
+    }
+}
@@ -1 +1,283 @@
 pub mod client;
+pub mod pool;
+pub mod response;
+pub mod tls;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task::JoinSet;
+    use tokio::time::{sleep, timeout};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::errors::client::{ConnectionClosedError, IncompleteResponseError, InvalidResponseError};
+    use crate::errors::slaves::SlaveFailedError;
+    use crate::models::request::HttpRequest;
+    use crate::models::response::HttpResponse;
+    use super::*;
+
+    // Feeds the message to a client parser a few bytes at a time
+    pub(crate) fn parse_response(message: &[u8], head_only: bool, step: usize) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+        let mut parser = response::ResponseParser::new(head_only);
+
+        for chunk in message.chunks(step) {
+            parser.push(chunk);
+
+            if let Some(res) = parser.parse(false)? {
+                return Ok(res);
+            }
+        }
+
+        parser.parse(true)?.ok_or("Response not returned".into())
+    }
+
+    #[test]
+    fn client_response_success() {
+        let chunked = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3;ext=1\r\nabc\r\n2\r\nde\r\n0\r\nX-Trailer: 1\r\n\r\n";
+        let length = b"HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\n\r\nnope";
+        let close = b"HTTP/1.0 200 OK\r\n\r\nuntil close";
+
+        for step in [1, 3, 7, 1024] {
+            let res = parse_response(chunked, false, step).unwrap();
+            assert_eq!(res.status, 200);
+            assert_eq!(res.contents_str(), "abcde");
+            assert!(!res.is_chunked());
+
+            let res = parse_response(length, false, step).unwrap();
+            assert_eq!((res.status, res.reason.as_str()), (404, "Not Found"));
+            assert_eq!(res.contents_str(), "nope");
+
+            let res = parse_response(close, false, step).unwrap();
+            assert_eq!(res.contents_str(), "until close");
+        }
+
+        // Responses to HEAD requests only have the head
+        let res = parse_response(b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\n", true, 1024).unwrap();
+        assert_eq!(res.headers.get("Content-Length").unwrap(), "10");
+        assert!(res.contents.is_empty());
+    }
+
+    #[test]
+    fn client_response_error() {
+        let invalid = [&b"SSH-2.0-OpenSSH\r\n\r\n"[..], b"HTTP/1.1 abc OK\r\n\r\n", b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"];
+
+        for message in invalid {
+            assert!(parse_response(message, false, 1024).unwrap_err().is::<InvalidResponseError>());
+        }
+
+        let incomplete = [&b""[..], b"HTTP/1.1 200 OK\r\n", b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort", b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n"];
+
+        for message in incomplete {
+            assert!(parse_response(message, false, 1024).unwrap_err().is::<IncompleteResponseError>());
+        }
+    }
+
+    // Answers every request with the given response, counting the connections
+    // accepted and the ones the client closed
+    async fn start_counting_server(response: &'static str, close_after: bool) -> (SocketAddr, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let accepted = Arc::new(AtomicUsize::new(0));
+        let closed = Arc::new(AtomicUsize::new(0));
+        let (accepted_clone, closed_clone) = (Arc::clone(&accepted), Arc::clone(&closed));
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                accepted_clone.fetch_add(1, Ordering::SeqCst);
+                let closed = Arc::clone(&closed_clone);
+
+                tokio::spawn(async move {
+                    let mut request = vec![];
+                    let mut buf = [0u8; 1024];
+
+                    loop {
+                        let read = stream.read(&mut buf).await.unwrap_or(0);
+
+                        if read == 0 {
+                            closed.fetch_add(1, Ordering::SeqCst);
+                            return;
+                        }
+
+                        request.extend_from_slice(&buf[..read]);
+
+                        if !request.ends_with(b"\r\n\r\n") {
+                            continue;
+                        }
+
+                        request.clear();
+                        stream.write_all(response.as_bytes()).await.unwrap();
+
+                        if close_after {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+
+        (address, accepted, closed)
+    }
+
+    fn pool_request() -> HttpRequest {
+        HttpRequest {
+            method: "GET".to_string(),
+            uri: vec!["status".to_string()],
+            version: "HTTP/1.1".to_string(),
+            ..Default::default()
+        }
+    }
+
+    const POOL_RESPONSE: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+
+    #[tokio::test]
+    async fn pool_reuse_success() {
+        let (address, accepted, _) = start_counting_server(POOL_RESPONSE, false).await;
+        let pool = pool::Pool::new(pool::PoolConfig::default());
+        let token = CancellationToken::new();
+
+        for _ in 0..3 {
+            let res = pool.send(address, &token, pool_request()).await.unwrap();
+            assert_eq!(res.contents_str(), "ok");
+        }
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn pool_max_per_host_success() {
+        let (address, accepted, _) = start_counting_server(POOL_RESPONSE, false).await;
+        let pool = pool::Pool::new(pool::PoolConfig { max_per_host: 1, ..Default::default() });
+        let token = CancellationToken::new();
+        let mut tasks = JoinSet::new();
+
+        for _ in 0..5 {
+            let (pool, token) = (pool.clone(), token.clone());
+            tasks.spawn(async move { pool.send(address, &token, pool_request()).await.unwrap() });
+        }
+
+        while let Some(res) = tasks.join_next().await {
+            assert_eq!(res.unwrap().status, 200);
+        }
+
+        // Requests waited for the only connection instead of opening more
+        assert_eq!(accepted.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn pool_closed_connection_success() {
+        // Closed without telling the client, so only the health check or the
+        // retry notice it
+        let (address, accepted, _) = start_counting_server(POOL_RESPONSE, true).await;
+        let pool = pool::Pool::new(pool::PoolConfig::default());
+        let token = CancellationToken::new();
+
+        pool.send(address, &token, pool_request()).await.unwrap();
+        sleep(Duration::from_millis(100)).await;
+        pool.send(address, &token, pool_request()).await.unwrap();
+        pool.send(address, &token, pool_request()).await.unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 3);
+
+        // Connections the server said it would close aren't kept
+        let (address, accepted, _) = start_counting_server("HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 2\r\n\r\nok", false).await;
+
+        for _ in 0..2 {
+            pool.send(address, &token, pool_request()).await.unwrap();
+        }
+
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn pool_evict_success() {
+        let (address, accepted, closed) = start_counting_server(POOL_RESPONSE, false).await;
+        let pool = pool::Pool::new(pool::PoolConfig::default());
+        let token = CancellationToken::new();
+
+        pool.send(address, &token, pool_request()).await.unwrap();
+        token.cancel();
+
+        timeout(Duration::from_secs(2), async {
+            while closed.load(Ordering::SeqCst) == 0 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        }).await.unwrap();
+
+        let e = pool.send(address, &token, pool_request()).await.unwrap_err();
+        assert!(e.is::<SlaveFailedError>());
+
+        // The slave registered again gets new connections
+        pool.send(address, &CancellationToken::new(), pool_request()).await.unwrap();
+        assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    }
+
+    // Reads a request without a body
+    async fn read_head(stream: &mut TcpStream) {
+        let mut request = vec![];
+        let mut buf = [0u8; 1024];
+
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buf).await.unwrap();
+            assert!(read > 0, "Connection closed before the request ended");
+            request.extend_from_slice(&buf[..read]);
+        }
+    }
+
+    // Answers the first request of each connection and closes the connection
+    // after reading the next one, counting the requests read
+    async fn start_dropping_server() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let requests_clone = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let requests = Arc::clone(&requests_clone);
+
+                tokio::spawn(async move {
+                    for answered in [true, false] {
+                        read_head(&mut stream).await;
+                        requests.fetch_add(1, Ordering::SeqCst);
+
+                        if answered {
+                            stream.write_all(POOL_RESPONSE.as_bytes()).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+
+        (address, requests)
+    }
+
+    #[tokio::test]
+    async fn pool_resend_error() {
+        let (address, requests) = start_dropping_server().await;
+        let pool = pool::Pool::new(pool::PoolConfig::default());
+        let token = CancellationToken::new();
+
+        let mut req = pool_request();
+        req.method = "POST".to_string();
+        pool.send(address, &token, req.clone()).await.unwrap();
+
+        // The slave may have done it, so it isn't sent again
+        let e = pool.send(address, &token, req).await.unwrap_err();
+        assert!(e.is::<ConnectionClosedError>());
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // GET requests can be repeated, so they go through a new connection
+        let (address, requests) = start_dropping_server().await;
+        pool.send(address, &token, pool_request()).await.unwrap();
+        let res = pool.send(address, &token, pool_request()).await.unwrap();
+
+        assert_eq!(res.contents_str(), "ok");
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use tokio::io::{AsyncWriteExt, ReadBuf};
+use tokio::sync::Semaphore;
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::client::{ConnectionClosedError, RequestNotSentError};
+use crate::errors::slaves::SlaveFailedError;
+use crate::models::request::HttpRequest;
+use crate::models::response::HttpResponse;
+use crate::server_base::limits::{read_secs, read_var};
+
+use super::client::{self, Stream};
+use super::response::ReadResult;
+use super::tls::{self, ClientTls};
+
+static POOL: LazyLock<Pool> = LazyLock::new(|| Pool::new(PoolConfig::from_env()));
+
+// Bounds of the connections kept to each slave. Each value can be overridden
+// with an env var
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    // Unused connections kept per slave, extra ones are closed
+    pub max_idle: usize,
+    // Requests sent to a slave at once, the rest wait for one to finish
+    pub max_per_host: usize,
+    // Time an unused connection is kept. Slaves close them after their
+    // keep-alive timeout, so it should be shorter than that
+    pub idle_timeout: Duration,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        PoolConfig {
+            max_idle: 16,
+            // Slaves answer 32 requests at once by default, the rest get 503
+            max_per_host: 32,
+            idle_timeout: Duration::from_secs(4),
+        }
+    }
+}
+
+impl PoolConfig {
+    pub fn from_env() -> PoolConfig {
+        let default = PoolConfig::default();
+
+        PoolConfig {
+            max_idle: read_var("POOL_MAX_IDLE").unwrap_or(default.max_idle),
+            max_per_host: read_var("POOL_MAX_PER_HOST").filter(|max| *max > 0).unwrap_or(default.max_per_host),
+            idle_timeout: read_secs("POOL_IDLE_TIMEOUT").unwrap_or(default.idle_timeout),
+        }
+    }
+}
+
+struct Idle {
+    stream: Box<dyn Stream>,
+    since: Instant,
+}
+
+// Connections to a slave while it's registered
+struct Host {
+    token: CancellationToken,
+    permits: Arc<Semaphore>,
+    // The most recently used connection is last
+    idle: Vec<Idle>,
+}
+
+// Keeps connections to the slaves open between requests, so tasks split in
+// many parts don't need a connection for each of them
+#[derive(Clone)]
+pub struct Pool {
+    hosts: Arc<Mutex<HashMap<SocketAddr, Host>>>,
+    config: PoolConfig,
+}
+
+impl Pool {
+    pub fn new(config: PoolConfig) -> Pool {
+        Pool { hosts: Arc::new(Mutex::new(HashMap::new())), config }
+    }
+
+    // Uses TLS if it was configured for the process
+    pub async fn send(&self, socket: SocketAddr, token: &CancellationToken, req: HttpRequest) -> ReadResult<HttpResponse> {
+        self.send_tls(socket, token, req, tls::configured()).await
+    }
+
+    // Uses an idle connection to the slave if there's a usable one, or a new
+    // one otherwise. Its connections are closed once the token is cancelled
+    pub async fn send_tls(&self, socket: SocketAddr, token: &CancellationToken, req: HttpRequest, tls: Option<&ClientTls>) -> ReadResult<HttpResponse> {
+        let permits = self.host(socket, token)?;
+        let _permit = permits.acquire().await.map_err(|_| SlaveFailedError)?;
+
+        let head_only = req.method == "HEAD";
+        let repeatable = req.method == "GET" || head_only;
+        let message = client::with_host(socket, req).to_bytes();
+
+        // The slave can close an idle connection right as we use it. The
+        // request is sent again on a new one if it never reached the slave, or
+        // if it can be repeated and the slave closed without answering
+        if let Some(mut stream) = self.checkout(socket)
+            && stream.write_all(&message).await.is_ok() {
+            match client::read_response(&mut stream, head_only).await {
+                Ok((res, reusable)) => {
+                    if reusable {
+                        self.release(socket, stream);
+                    }
+
+                    return Ok(res);
+                },
+                Err(e) if !(repeatable && e.is::<ConnectionClosedError>()) => return Err(e),
+                Err(_) => {},
+            }
+        }
+
+        // Up to here, the slave can't have handled the request
+        let mut stream = client::connect(socket, tls).await.map_err(|_| RequestNotSentError)?;
+        stream.write_all(&message).await.map_err(|_| RequestNotSentError)?;
+
+        let (res, reusable) = client::read_response(&mut stream, head_only).await?;
+
+        if reusable {
+            self.release(socket, stream);
+        }
+
+        Ok(res)
+    }
+
+    // The slot limiting the slave's requests. Slaves registered again after
+    // being removed get a new token, so they start over
+    fn host(&self, socket: SocketAddr, token: &CancellationToken) -> ReadResult<Arc<Semaphore>> {
+        if token.is_cancelled() {
+            return Err(Box::new(SlaveFailedError));
+        }
+
+        let mut hosts = self.hosts.lock();
+
+        if let Some(host) = hosts.get(&socket).filter(|host| !host.token.is_cancelled()) {
+            return Ok(Arc::clone(&host.permits));
+        }
+
+        let permits = Arc::new(Semaphore::new(self.config.max_per_host));
+        hosts.insert(socket, Host { token: token.clone(), permits: Arc::clone(&permits), idle: vec![] });
+
+        let pool = self.clone();
+        let token = token.clone();
+
+        tokio::spawn(async move {
+            token.cancelled().await;
+            pool.evict(socket);
+        });
+
+        Ok(permits)
+    }
+
+    // Connections to a removed slave are useless, and requests waiting for
+    // one fail right away
+    fn evict(&self, socket: SocketAddr) {
+        let mut hosts = self.hosts.lock();
+
+        if hosts.get(&socket).is_some_and(|host| host.token.is_cancelled())
+            && let Some(host) = hosts.remove(&socket) {
+            host.permits.close();
+        }
+    }
+
+    // Idle connections that expired or were closed by the slave are dropped
+    fn checkout(&self, socket: SocketAddr) -> Option<Box<dyn Stream>> {
+        let mut hosts = self.hosts.lock();
+        let host = hosts.get_mut(&socket)?;
+
+        while let Some(mut idle) = host.idle.pop() {
+            if idle.since.elapsed() < self.config.idle_timeout && is_open(&mut idle.stream) {
+                return Some(idle.stream);
+            }
+        }
+
+        None
+    }
+
+    fn release(&self, socket: SocketAddr, stream: Box<dyn Stream>) {
+        let mut hosts = self.hosts.lock();
+
+        let Some(host) = hosts.get_mut(&socket).filter(|host| !host.token.is_cancelled()) else {
+            return;
+        };
+
+        host.idle.retain(|idle| idle.since.elapsed() < self.config.idle_timeout);
+
+        if host.idle.len() < self.config.max_idle {
+            host.idle.push(Idle { stream, since: Instant::now() });
+        }
+    }
+}
+
+pub async fn send(socket: SocketAddr, token: &CancellationToken, req: HttpRequest) -> ReadResult<HttpResponse> {
+    POOL.send(socket, token, req).await
+}
+
+// No request was sent on an idle connection, so there should be nothing to
+// read. Anything else means the slave closed it or sent something unexpected
+fn is_open(stream: &mut Box<dyn Stream>) -> bool {
+    let mut probe = [0u8; 1];
+    let mut buf = ReadBuf::new(&mut probe);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    matches!(Pin::new(stream.as_mut()).poll_read(&mut cx, &mut buf), Poll::Pending)
+}
@@ -0,0 +1,215 @@
+use crate::errors::client::{IncompleteResponseError, InvalidResponseError};
+use crate::models::headers::Headers;
+use crate::models::response::HttpResponse;
+use crate::server_base::parser::find;
+
+pub type ReadResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Bytes the status line and headers can take
+const MAX_HEAD_SIZE: usize = 64 * 1024;
+
+// How the end of the body is known
+#[derive(Debug, Clone, Copy)]
+enum Framing {
+    // Responses to HEAD requests, 204 and 304 ones never have a body
+    Empty,
+    Length(usize),
+    Chunked,
+    // The body ends when the server closes the connection
+    Close,
+}
+
+// Builds a response from the bytes received so far, so the same logic works
+// for the async and sync clients, however the bytes are split
+pub struct ResponseParser {
+    buffer: Vec<u8>,
+    head_only: bool,
+    head: Option<(HttpResponse, Framing)>,
+    // Where the part of the body not yet handled starts
+    position: usize,
+    // Chunked bodies are decoded as they arrive
+    decoded: Vec<u8>,
+    trailers: bool,
+    // Whether the connection can take another request once the response is
+    // complete
+    reusable: bool,
+}
+
+impl ResponseParser {
+    pub fn new(head_only: bool) -> ResponseParser {
+        ResponseParser { buffer: vec![], head_only, head: None, position: 0, decoded: vec![], trailers: false, reusable: false }
+    }
+
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    // Returns the response once it's complete. Once the connection is closed,
+    // it's either complete or an error
+    pub fn parse(&mut self, closed: bool) -> ReadResult<Option<HttpResponse>> {
+        if self.head.is_none() && !self.parse_head()? {
+            return match closed {
+                true => Err(Box::new(IncompleteResponseError)),
+                false => Ok(None),
+            };
+        }
+
+        let Some(framing) = self.head.as_ref().map(|(_, framing)| *framing) else {
+            return Ok(None);
+        };
+
+        let body = match framing {
+            Framing::Empty => Some(vec![]),
+            Framing::Length(length) if self.buffer.len() - self.position >= length => {
+                let body = self.buffer[self.position..self.position + length].to_vec();
+                self.position += length;
+                Some(body)
+            },
+            Framing::Length(_) => None,
+            Framing::Chunked if self.decode_chunks()? => Some(std::mem::take(&mut self.decoded)),
+            Framing::Chunked => None,
+            Framing::Close if closed => Some(self.buffer[self.position..].to_vec()),
+            Framing::Close => None,
+        };
+
+        let Some(body) = body else {
+            return match closed {
+                true => Err(Box::new(IncompleteResponseError)),
+                false => Ok(None),
+            };
+        };
+
+        let Some((mut res, framing)) = self.head.take() else {
+            return Ok(None);
+        };
+
+        // Bytes after the response mean the server sent something we didn't
+        // ask for, so the connection can't be trusted with another request
+        self.reusable = !matches!(framing, Framing::Close)
+            && self.position == self.buffer.len()
+            && keeps_alive(&res);
+
+        // The body is already decoded, so it no longer uses chunks
+        if matches!(framing, Framing::Chunked) {
+            res.headers.remove("Transfer-Encoding");
+        }
+
+        res.set_received_contents(body);
+        Ok(Some(res))
+    }
+
+    // Only meaningful once the response was returned
+    pub fn reusable(&self) -> bool {
+        self.reusable
+    }
+
+    // Returns whether the whole head was received
+    fn parse_head(&mut self) -> ReadResult<bool> {
+        let Some(end) = find(&self.buffer, b"\r\n\r\n") else {
+            if self.buffer.len() > MAX_HEAD_SIZE {
+                return Err(Box::new(InvalidResponseError));
+            }
+
+            return Ok(false);
+        };
+
+        let head = std::str::from_utf8(&self.buffer[..end]).map_err(|_| InvalidResponseError)?;
+        let mut lines = head.split("\r\n");
+
+        let mut status_line = lines.next().unwrap_or("").splitn(3, " ");
+        let version = status_line.next().filter(|version| version.starts_with("HTTP/")).ok_or(InvalidResponseError)?;
+        let status = status_line.next()
+            .and_then(|status| status.parse::<u16>().ok())
+            .filter(|status| (100..1000).contains(status))
+            .ok_or(InvalidResponseError)?;
+        let reason = status_line.next().unwrap_or("").to_string();
+
+        let mut headers = Headers::new();
+
+        for line in lines {
+            let (name, value) = line.split_once(":").ok_or(InvalidResponseError)?;
+            headers.append(name.trim().to_string(), value.trim().to_string());
+        }
+
+        // Interim responses, like 100 Continue, come before the actual one
+        if (100..200).contains(&status) && status != 101 {
+            self.buffer.drain(..end + 4);
+            return self.parse_head();
+        }
+
+        let mut res = HttpResponse::new(version.to_string(), status, headers, vec![]);
+        res.reason = reason;
+
+        let framing = if self.head_only || status < 200 || status == 204 || status == 304 {
+            Framing::Empty
+        } else if res.is_chunked() {
+            Framing::Chunked
+        } else if let Some(length) = res.headers.get("Content-Length") {
+            Framing::Length(length.parse::<usize>().map_err(|_| InvalidResponseError)?)
+        } else {
+            Framing::Close
+        };
+
+        self.position = end + 4;
+        self.head = Some((res, framing));
+        Ok(true)
+    }
+
+    // Decodes the chunks received so far, returning whether the last one and
+    // the trailers, which are ignored, were received
+    fn decode_chunks(&mut self) -> ReadResult<bool> {
+        loop {
+            let Some(end) = find(&self.buffer[self.position..], b"\r\n") else {
+                return Ok(false);
+            };
+
+            let line = &self.buffer[self.position..self.position + end];
+
+            // After the last chunk, trailers end with an empty line
+            if self.trailers {
+                self.position += end + 2;
+
+                if line.is_empty() {
+                    return Ok(true);
+                }
+
+                continue;
+            }
+
+            let line = std::str::from_utf8(line).map_err(|_| InvalidResponseError)?;
+            let size = line.split(";").next().unwrap_or("").trim();
+            let size = usize::from_str_radix(size, 16).map_err(|_| InvalidResponseError)?;
+
+            if size == 0 {
+                self.position += end + 2;
+                self.trailers = true;
+                continue;
+            }
+
+            // Each chunk's data is followed by a CRLF
+            let start = self.position + end + 2;
+            let chunk_end = start.checked_add(size).ok_or(InvalidResponseError)?;
+
+            if self.buffer.len() < chunk_end + 2 {
+                return Ok(false);
+            }
+
+            if &self.buffer[chunk_end..chunk_end + 2] != b"\r\n" {
+                return Err(Box::new(InvalidResponseError));
+            }
+
+            self.decoded.extend_from_slice(&self.buffer[start..chunk_end]);
+            self.position = chunk_end + 2;
+        }
+    }
+}
+
+// HTTP/1.1 connections stay open unless the server says otherwise, HTTP/1.0
+// ones only if it says so. Switched protocols no longer speak HTTP
+fn keeps_alive(res: &HttpResponse) -> bool {
+    if res.status == 101 || res.headers.has_token("Connection", "close") {
+        return false;
+    }
+
+    res.version != "HTTP/1.0" || res.headers.has_token("Connection", "keep-alive")
+}
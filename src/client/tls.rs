@@ -0,0 +1,69 @@
+use std::env;
+use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tokio_rustls::rustls::pki_types::{InvalidDnsNameError, ServerName};
+
+use crate::errors::log_error;
+use crate::server_base::tls::load_certs;
+
+type TlsResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+static CLIENT_TLS: LazyLock<Option<ClientTls>> = LazyLock::new(from_env);
+
+// Settings to send requests over TLS, trusting only the configured CA. Nodes
+// are reached by IP, so unless a server name is given, their certificates
+// need to include the IP address
+#[derive(Debug, Clone)]
+pub struct ClientTls {
+    config: Arc<ClientConfig>,
+    server_name: Option<String>,
+}
+
+impl ClientTls {
+    pub fn new(ca_path: &str, server_name: Option<String>) -> TlsResult<ClientTls> {
+        let mut roots = RootCertStore::empty();
+
+        for cert in load_certs(ca_path)? {
+            roots.add(cert)?;
+        }
+
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Ok(ClientTls { config: Arc::new(config), server_name })
+    }
+
+    pub fn config(&self) -> Arc<ClientConfig> {
+        Arc::clone(&self.config)
+    }
+
+    // The name the certificate is checked against
+    pub fn server_name(&self, socket: SocketAddr) -> Result<ServerName<'static>, InvalidDnsNameError> {
+        match &self.server_name {
+            Some(name) => ServerName::try_from(name.clone()),
+            None => Ok(ServerName::from(socket.ip())),
+        }
+    }
+}
+
+// Requests use TLS once a CA is configured. If it can't be loaded, we can't
+// reach the other nodes as requested, so it's unrecoverable
+fn from_env() -> Option<ClientTls> {
+    let Ok(ca_path) = env::var("TLS_CA_PATH") else {
+        return None;
+    };
+
+    match ClientTls::new(&ca_path, env::var("TLS_SERVER_NAME").ok()) {
+        Ok(tls) => Some(tls),
+        Err(e) => {
+            log_error(format!("Unable to load TLS CA: {}", e).into());
+            panic!("Unrecoverable error! Check logs.");
+        },
+    }
+}
+
+pub fn configured() -> Option<&'static ClientTls> {
+    CLIENT_TLS.as_ref()
+}
@@ -0,0 +1,45 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone)]
+pub struct InvalidResponseError;
+
+impl fmt::Display for InvalidResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "response is not valid HTTP")
+    }
+}
+
+impl Error for InvalidResponseError {}
+
+#[derive(Debug, Clone)]
+pub struct IncompleteResponseError;
+
+impl fmt::Display for IncompleteResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "connection closed before the response was complete")
+    }
+}
+
+impl Error for IncompleteResponseError {}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionClosedError;
+
+impl fmt::Display for ConnectionClosedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "connection closed before any response was received")
+    }
+}
+
+impl Error for ConnectionClosedError {}
+
+#[derive(Debug, Clone)]
+pub struct RequestNotSentError;
+
+impl fmt::Display for RequestNotSentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request couldn't be sent to the server")
+    }
+}
+
+impl Error for RequestNotSentError {}
@@ -0,0 +1,34 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone)]
+pub struct FileExistsError;
+
+impl fmt::Display for FileExistsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "A file with that name already exists!")
+    }
+}
+
+impl Error for FileExistsError {}
+
+#[derive(Debug, Clone)]
+pub struct InvalidFileNameError;
+
+impl fmt::Display for InvalidFileNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid file name!")
+    }
+}
+
+impl Error for InvalidFileNameError {}
+
+#[derive(Debug, Clone)]
+pub struct FileTooLargeError;
+
+impl fmt::Display for FileTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The file exceeds the maximum size allowed!")
+    }
+}
+
+impl Error for FileTooLargeError {}
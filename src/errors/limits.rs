@@ -0,0 +1,45 @@
+use std::{error::Error, fmt};
+
+#[derive(Debug, Clone)]
+pub struct UriTooLongError;
+
+impl fmt::Display for UriTooLongError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request line exceeds the allowed length")
+    }
+}
+
+impl Error for UriTooLongError {}
+
+#[derive(Debug, Clone)]
+pub struct HeadersTooLargeError;
+
+impl fmt::Display for HeadersTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request headers exceed the allowed count or size")
+    }
+}
+
+impl Error for HeadersTooLargeError {}
+
+#[derive(Debug, Clone)]
+pub struct PayloadTooLargeError;
+
+impl fmt::Display for PayloadTooLargeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request body exceeds the allowed size")
+    }
+}
+
+impl Error for PayloadTooLargeError {}
+
+#[derive(Debug, Clone)]
+pub struct RequestTimeoutError;
+
+impl fmt::Display for RequestTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "request was not received in the allowed time")
+    }
+}
+
+impl Error for RequestTimeoutError {}
@@ -4,11 +4,16 @@ pub mod implement;
 pub mod pool;
 pub mod matrix;
 pub mod slaves;
+pub mod limits;
+pub mod files;
+pub mod client;
 
 use std::io::Write;
 use std::{fs, time::SystemTime};
 use chrono::{self, DateTime, Utc};
 
+use crate::server_base::request_id;
+
 pub fn log_error(error: Box<dyn std::error::Error>) {
     let kind = "error".to_string();
     let timestamp = get_timestamp();
@@ -26,6 +31,8 @@ fn write_log(kind: String, message: String, timestamp: String, log: String) {
     fs::create_dir_all("./logs/").unwrap();
     let path = "logs/".to_string() + &log + ".log";
     let kind = kind.to_ascii_uppercase();
+    // Lines logged outside of a request keep the column
+    let id = request_id::current().unwrap_or("-".to_string());
 
     let mut file = fs::OpenOptions::new()
         .create(true)
@@ -33,7 +40,7 @@ fn write_log(kind: String, message: String, timestamp: String, log: String) {
         .open(path)
         .unwrap();
 
-    let message = kind + "\t" + &timestamp + "\t" + &message;
+    let message = kind + "\t" + &timestamp + "\t" + &id + "\t" + &message;
     
     writeln!(file, "{}", message).unwrap();
 }
@@ -21,3 +21,36 @@ impl fmt::Display for SlaveFailedError {
 }
 
 impl Error for SlaveFailedError {}
+
+#[derive(Debug, Clone)]
+pub struct SlaveTimeoutError;
+
+impl fmt::Display for SlaveTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Slave took too long to answer!")
+    }
+}
+
+impl Error for SlaveTimeoutError {}
+
+#[derive(Debug, Clone)]
+pub struct RetriesExhaustedError;
+
+impl fmt::Display for RetriesExhaustedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Slaves failed every attempt of the request!")
+    }
+}
+
+impl Error for RetriesExhaustedError {}
+
+#[derive(Debug, Clone)]
+pub struct SlaveResponseLostError;
+
+impl fmt::Display for SlaveResponseLostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Slave failed after receiving the request!")
+    }
+}
+
+impl Error for SlaveResponseLostError {}
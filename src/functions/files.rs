@@ -0,0 +1,109 @@
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::errors::files::{FileExistsError, FileTooLargeError, InvalidFileNameError};
+use crate::redis_comm::file_store;
+
+// Directory with the files the cluster works on, like the /countwords ones
+pub const FILES_DIR: &str = "archivos";
+
+const DEFAULT_MAX_UPLOAD_SIZE: usize = 8 * 1024 * 1024;
+
+// Bytes an uploaded file can have, set with MAX_UPLOAD_SIZE. Bodies are also
+// bounded by MAX_BODY_SIZE, so this only matters if it's the lower one
+pub fn max_upload_size() -> usize {
+    env::var("MAX_UPLOAD_SIZE").ok()
+        .and_then(|size| size.parse::<usize>().ok())
+        .unwrap_or(DEFAULT_MAX_UPLOAD_SIZE)
+}
+
+// Names are used as-is in paths and Redis keys, so only plain names are
+// allowed: no separators, no hidden files and nothing that isn't printable
+pub fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 255
+        && !name.starts_with(".")
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(FILES_DIR).join(name)
+}
+
+// Stores an uploaded file in Redis, so every slave can read it, and in this
+// node's directory. Redis decides which upload gets a name, as it's shared
+pub fn store(name: &str, content: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    if !valid_name(name) {
+        return Err(Box::new(InvalidFileNameError));
+    }
+
+    if content.len() > max_upload_size() {
+        return Err(Box::new(FileTooLargeError));
+    }
+
+    // Files that came with the image aren't in Redis
+    if fs::exists(path(name))? || !file_store::add_file(name, content)? {
+        return Err(Box::new(FileExistsError));
+    }
+
+    if let Err(e) = write_new(name, content) {
+        let _ = file_store::remove_file(name);
+
+        if e.kind() == ErrorKind::AlreadyExists {
+            return Err(Box::new(FileExistsError));
+        }
+
+        return Err(Box::new(e));
+    }
+
+    Ok(())
+}
+
+fn write_new(name: &str, content: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all(FILES_DIR)?;
+
+    let mut file = fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(path(name))?;
+
+    file.write_all(content)
+}
+
+// Whether the file is in this node's directory or was uploaded to any node
+pub fn exists(name: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if !valid_name(name) {
+        return Ok(false);
+    }
+
+    Ok(fs::exists(path(name))? || file_store::has_file(name)?)
+}
+
+// Reads a file from this node's directory or, if it was uploaded to another
+// node, from Redis
+pub fn read(name: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    if !valid_name(name) {
+        return Err(Box::new(InvalidFileNameError));
+    }
+
+    match fs::read(path(name)) {
+        Ok(content) => Ok(content),
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            file_store::get_file(name)?.ok_or_else(|| Box::new(e) as Box<dyn std::error::Error>)
+        },
+        Err(e) => Err(Box::new(e)),
+    }
+}
+
+// Only files in this node's directory have a modification time, as Redis
+// doesn't keep one
+pub fn modified(name: &str) -> Option<SystemTime> {
+    if !valid_name(name) {
+        return None;
+    }
+
+    fs::metadata(path(name)).and_then(|metadata| metadata.modified()).ok()
+}
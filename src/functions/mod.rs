@@ -8,7 +8,7 @@ pub mod timestamp;
 pub mod hash;
 pub mod simulate;
 pub mod sleep;
-pub mod help;
+pub mod files;
 
 #[cfg(test)]
 mod tests {
@@ -185,4 +185,17 @@ mod tests {
         let result = toupper::toupper("hello world!");
         assert_eq!("HELLO WORLD!", result);
     }
+
+    #[test]
+    fn file_name_success() {
+        assert!(files::valid_name("Metamorfosis.txt"));
+        assert!(files::valid_name("result_2-b.json"));
+    }
+
+    #[test]
+    fn file_name_error() {
+        for name in ["", ".env", "../a.txt", "a/b.txt", "a\\b.txt", "a b.txt", "año.txt", &"a".repeat(256)] {
+            assert!(!files::valid_name(name), "{} was accepted", name);
+        }
+    }
 }
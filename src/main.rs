@@ -33,6 +33,10 @@ async fn main() {
     let slave_code = check_env_var("SLAVE_CODE", true);
     let role = check_env_var("SERVER_ROLE", false);
     
+    // SIGTERM and SIGINT start a graceful shutdown instead of killing the
+    // in-flight requests
+    tokio::spawn(server_base::shutdown::listen());
+    
     // We default to slave role in case of error reading or matching the value
     if role == "MASTER" {
         log_info(format!("Starting server as master on port {}", port));
@@ -0,0 +1,92 @@
+use std::slice::Iter;
+
+// Header collection for requests and responses. Names are matched ignoring
+// case, as HTTP requires, and a name can hold multiple values. The original
+// order and casing are kept so messages are forwarded as they were received
+#[derive(Default, Debug, Clone)]
+pub struct Headers {
+    entries: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn new() -> Headers {
+        Headers { entries: vec![] }
+    }
+
+    // Returns the first value for the given name
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+    }
+
+    // Returns every value for the given name, in the order received
+    pub fn get_all(&self, name: &str) -> Vec<&String> {
+        self.entries.iter()
+            .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    // Checks whether any value of a comma-separated header, like Connection
+    // or Transfer-Encoding, has the given token
+    pub fn has_token(&self, name: &str, token: &str) -> bool {
+        self.get_all(name).iter()
+            .flat_map(|value| value.split(","))
+            .any(|value| value.trim().eq_ignore_ascii_case(token))
+    }
+
+    // Adds a token to a comma-separated header, like Vary, unless it's there
+    pub fn add_token(&mut self, name: &str, token: &str) {
+        if self.has_token(name, token) {
+            return;
+        }
+
+        let value = match self.get(name) {
+            Some(value) => format!("{}, {}", value, token),
+            None => token.to_string(),
+        };
+
+        self.insert(name.to_string(), value);
+    }
+
+    // Replaces any existing value for the name
+    pub fn insert(&mut self, name: String, value: String) {
+        self.remove(&name);
+        self.entries.push((name, value));
+    }
+
+    // Adds a value, keeping the existing ones for the same name
+    pub fn append(&mut self, name: String, value: String) {
+        self.entries.push((name, value));
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.entries.retain(|(k, _)| !k.eq_ignore_ascii_case(name));
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> Iter<'_, (String, String)> {
+        self.entries.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Headers {
+    type Item = &'a (String, String);
+    type IntoIter = Iter<'a, (String, String)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.iter()
+    }
+}
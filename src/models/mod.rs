@@ -1,6 +1,43 @@
 pub mod count;
+pub mod headers;
 pub mod matrix;
 pub mod request;
 pub mod response;
 pub mod status;
 pub mod slave;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_case_insensitive() {
+        let mut headers = headers::Headers::new();
+        headers.insert("Content-Length".to_string(), "10".to_string());
+
+        assert_eq!(headers.get("content-length").unwrap(), "10");
+        assert_eq!(headers.get("CONTENT-LENGTH").unwrap(), "10");
+        assert!(headers.get("Content-Type").is_none());
+
+        // Inserting with another casing replaces the value
+        headers.insert("content-length".to_string(), "20".to_string());
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.get("Content-Length").unwrap(), "20");
+    }
+
+    #[test]
+    fn headers_multiple_values() {
+        let mut headers = headers::Headers::new();
+        headers.append("Accept".to_string(), "text/plain".to_string());
+        headers.append("accept".to_string(), "application/json".to_string());
+        headers.append("Connection".to_string(), "keep-alive, Upgrade".to_string());
+
+        assert_eq!(headers.get("Accept").unwrap(), "text/plain");
+        assert_eq!(headers.get_all("ACCEPT"), vec!["text/plain", "application/json"]);
+        assert!(headers.has_token("connection", "upgrade"));
+        assert!(!headers.has_token("connection", "close"));
+
+        headers.remove("Accept");
+        assert_eq!(headers.len(), 1);
+    }
+}
@@ -1,15 +1,22 @@
 use std::fmt;
 use std::collections::HashMap;
 
+use super::headers::Headers;
+
+use crate::server_base::parser::percent_encode;
+
 #[derive(Debug, Clone)]
 pub enum Body {
     JSON(String),
-    URLdec(()),
+    // Decoded key-value pairs, in the order received
+    URLdec(Vec<(String, String)>),
+    // Any other type, as received. The Content-Type header tells what it is
+    Binary(Vec<u8>),
 }
 
 impl Default for Body {
     fn default() -> Self {
-        Body::URLdec(())
+        Body::URLdec(vec![])
     }   
 }
 
@@ -19,12 +26,14 @@ pub struct HttpRequest {
     pub uri: Vec<String>,
     pub params: HashMap<String, String>,
     pub version: String,
-    pub headers: HashMap<String, String>,
+    pub headers: Headers,
     pub body: Body,
+    // Every query pair in the order received, including repeated keys
+    pub query: Vec<(String, String)>,
 }
 
 impl HttpRequest {
-    pub fn new(method: String, uri: Vec<String>, params: HashMap<String, String>, version: String, headers: HashMap<String, String>, body: Body) -> HttpRequest {
+    pub fn new(method: String, uri: Vec<String>, params: HashMap<String, String>, version: String, headers: Headers, body: Body) -> HttpRequest {
         HttpRequest {
             method,
             uri,
@@ -32,19 +41,61 @@ impl HttpRequest {
             version,
             headers,
             body,
+            query: vec![],
+        }
+    }
+
+    // OPTIONS requests can target the whole server instead of a path
+    pub fn is_asterisk(&self) -> bool {
+        self.method == "OPTIONS" && self.uri.len() == 1 && self.uri[0] == "*"
+    }
+
+    // Looks up a parameter, first in the query string and then in the
+    // x-www-form-urlencoded body, if any. Routes should use this instead of
+    // the params map so both sources are seen the same way
+    pub fn param(&self, key: &str) -> Option<&String> {
+        if let Some(value) = self.params.get(key) {
+            return Some(value);
+        }
+
+        match &self.body {
+            Body::URLdec(pairs) => pairs.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
         }
     }
+
+    // Returns every query value received for the given key. The query pairs
+    // are only used while the map still holds the first of them, so values
+    // changed in the map, or requests built in code, use the map instead
+    pub fn param_values(&self, key: &str) -> Vec<&String> {
+        let Some(value) = self.params.get(key) else {
+            return vec![];
+        };
+
+        let mut values = self.query.iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .peekable();
+
+        if values.peek() != Some(&value) {
+            return vec![value];
+        }
+
+        values.collect()
+    }
 }
 
-// We unparse the data structure into a HTTP-formatted string
-impl fmt::Display for HttpRequest {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl HttpRequest {
+    // We unparse the data structure into a HTTP-formatted message
+    pub fn to_bytes(&self) -> Vec<u8> {
         let mut uri = "/".to_string();
 
         // Parsed messages will have a '/' only if the resources is root
-        if self.uri[0] != "/" {
+        if self.is_asterisk() {
+            uri = "*".to_string();
+        } else if self.uri[0] != "/" {
             for section in &self.uri {
-                uri += section;
+                uri += &percent_encode(section);
                 uri += "/";
             }
     
@@ -57,42 +108,58 @@ impl fmt::Display for HttpRequest {
         if self.params.len() > 0 {
             params += "?";
             
-            for (k,v) in &self.params {
-                params += k;
-                params += "=";
-                params += v;
-                params += "&";
+            // Values were decoded by the parser, so we need to encode them
+            // again before forwarding the request
+            for k in self.params.keys() {
+                for v in self.param_values(k) {
+                    params += &percent_encode(k);
+                    params += "=";
+                    params += &percent_encode(v);
+                    params += "&";
+                }
             }
 
             // We remove the extra '&' added by the for-loop
             params.pop();
         }
 
+        let body = match &self.body {
+            Body::JSON(json) => json.as_bytes().to_vec(),
+            Body::URLdec(pairs) => pairs.iter()
+                .map(|(k, v)| format!("{}={}", percent_encode(k), percent_encode(v)))
+                .collect::<Vec<String>>()
+                .join("&")
+                .into_bytes(),
+            Body::Binary(content) => content.clone(),
+        };
+
         let mut headers = String::new();
 
-        if self.headers.len() > 0 {
-            for (k,v) in &self.headers {
-                headers += k;
-                headers += ": ";
-                headers += v;
-                headers += "\r\n";
+        // The body could've been re-encoded, so we always compute the length
+        // instead of trusting the one received
+        for (k,v) in &self.headers {
+            if k.eq_ignore_ascii_case("Content-Length") || k.eq_ignore_ascii_case("Transfer-Encoding") {
+                continue;
             }
+
+            headers += k;
+            headers += ": ";
+            headers += v;
+            headers += "\r\n";
         }
 
-        let body = match &self.body {
-            Body::JSON(json) => json.to_string(),
-            Body::URLdec(_) => "".to_string(),
-        };
+        if !body.is_empty() {
+            headers += &format!("Content-Length: {}\r\n", body.len());
+        }
 
-        write!(
-            f,
-            "{} {}{} {}\r\n{}\r\n{}",
-            self.method,
-            uri,
-            params,
-            self.version,
-            headers,
-            body
-        )
+        let mut message = format!("{} {}{} {}\r\n{}\r\n", self.method, uri, params, self.version, headers).into_bytes();
+        message.extend_from_slice(&body);
+        message
+    }
+}
+
+impl fmt::Display for HttpRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
     }
 }
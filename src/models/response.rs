@@ -1,12 +1,62 @@
+use std::borrow::Cow;
 use std::fmt;
-use std::collections::HashMap;
-use std::convert::From;
-use std::io::{BufRead, BufReader, Read};
 
-#[derive(Debug, Clone)]
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::Receiver;
+
+use crate::server_base::compression::{self, Encoding};
+
+use super::headers::Headers;
+
+#[derive(Debug)]
 pub enum Response {
     HTTP(HttpResponse),
-    Buffer(Vec<u8>)
+    // The head is sent right away and each received value is written as a
+    // chunk, until the sender is dropped
+    Stream(HttpResponse, Receiver<Vec<u8>>),
+}
+
+impl Response {
+    // The status and headers, which streams also have before their body
+    pub fn head_mut(&mut self) -> &mut HttpResponse {
+        match self {
+            Response::HTTP(res) => res,
+            Response::Stream(res, _) => res,
+        }
+    }
+}
+
+// Marks the end of a chunked body
+pub const LAST_CHUNK: &[u8] = b"0\r\n\r\n";
+
+// Frames the data as a single chunk of a chunked body
+pub fn encode_chunk(data: &[u8]) -> Vec<u8> {
+    let mut chunk = format!("{:X}\r\n", data.len()).into_bytes();
+    chunk.extend_from_slice(data);
+    chunk.extend_from_slice(b"\r\n");
+    chunk
+}
+
+// Value of the Server header, unless the handler sets its own
+pub const SERVER: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+// Used for bodies of responses without a Content-Type
+pub const DEFAULT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+// Format of dates in headers, like Date or Last-Modified
+pub fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+// How the end of the body is told to the client
+enum Framing {
+    // The body follows the head, so the length is always the actual one
+    Exact,
+    // A length set by the handler is kept, as with HEAD requests the body
+    // isn't sent or it's sent in another representation
+    Declared,
+    // The connection is closed after the body
+    Close,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -14,32 +64,134 @@ pub struct HttpResponse {
     pub version: String,
     pub status: u16,
     pub reason: String,
-    pub headers: HashMap<String, String>,
-    pub contents: String,
+    pub headers: Headers,
+    // Raw body, which can hold any kind of data
+    pub contents: Vec<u8>,
 }
 
 impl HttpResponse {
-    pub fn new(version: String, status: u16, headers: HashMap<String, String>, contents: String) -> HttpResponse {
+    pub fn new(version: String, status: u16, headers: Headers, contents: impl Into<Vec<u8>>) -> HttpResponse {
         let reason = Self::reason_from_status(status);
 
-        HttpResponse { version, status, reason, headers, contents }
+        HttpResponse { version, status, reason, headers, contents: contents.into() }
     }
 
     pub fn basic(status: u16) -> HttpResponse {
         let version = "HTTP/1.1".to_string();
-        let headers = HashMap::new();
-        let contents = "".to_string();
+        let headers = Headers::new();
 
-        Self::new(version, status, headers, contents)
+        Self::new(version, status, headers, vec![])
+    }
+
+    pub fn text(status: u16, contents: impl Into<String>) -> HttpResponse {
+        Self::binary(status, contents.into().into_bytes(), DEFAULT_CONTENT_TYPE)
+    }
+
+    pub fn json(status: u16, contents: impl Into<String>) -> HttpResponse {
+        Self::binary(status, contents.into().into_bytes(), "application/json")
+    }
+
+    pub fn binary(status: u16, contents: Vec<u8>, content_type: &str) -> HttpResponse {
+        let mut res = Self::basic(status);
+        res.headers.insert("Content-Type".to_string(), content_type.to_string());
+        res.contents = contents;
+        res
+    }
+
+    // The body as text, with invalid UTF-8 sequences replaced
+    pub fn contents_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.contents)
+    }
+
+    pub fn is_chunked(&self) -> bool {
+        self.headers.has_token("Transfer-Encoding", "chunked")
+    }
+
+    // Status line and headers, including the empty line that separates them
+    // from the body
+    pub fn head(&self) -> String {
+        self.framed_head(Framing::Declared)
+    }
+
+    // Head for a body that ends when the connection is closed
+    pub fn head_until_close(&self) -> String {
+        self.framed_head(Framing::Close)
+    }
+
+    fn framed_head(&self, framing: Framing) -> String {
+        let mut headers = "".to_string();
+
+        for (header, value) in &self.standard_headers(framing) {
+            headers = headers + header + ": " + value + "\r\n";
+        }
+
+        format!("{} {} {}\r\n{}\r\n", self.version, self.status, self.reason, headers)
+    }
+
+    // The handler's headers, plus the ones every response carries. Only the
+    // framing can't be overridden when the body is sent along
+    fn standard_headers(&self, framing: Framing) -> Headers {
+        let mut headers = self.headers.clone();
+
+        if !headers.contains("Date") {
+            headers.insert("Date".to_string(), http_date(Utc::now()));
+        }
+
+        if !headers.contains("Server") {
+            headers.insert("Server".to_string(), SERVER.to_string());
+        }
+
+        if !self.allows_body() {
+            return headers;
+        }
+
+        if !self.contents.is_empty() && !headers.contains("Content-Type") {
+            headers.insert("Content-Type".to_string(), DEFAULT_CONTENT_TYPE.to_string());
+        }
+
+        if self.is_chunked() {
+            headers.remove("Content-Length");
+            return headers;
+        }
+
+        match framing {
+            Framing::Exact => headers.insert("Content-Length".to_string(), self.contents.len().to_string()),
+            Framing::Declared if !headers.contains("Content-Length") => {
+                headers.insert("Content-Length".to_string(), self.contents.len().to_string());
+            },
+            Framing::Declared => {},
+            Framing::Close => headers.remove("Content-Length"),
+        }
+
+        headers
+    }
+
+    // Informational, 204 and 304 responses never have a body
+    fn allows_body(&self) -> bool {
+        self.status >= 200 && self.status != 204 && self.status != 304
     }
 
     fn reason_from_status(status: u16) -> String {
         (match status {
             200 => "OK",
+            201 => "Created",
+            204 => "No Content",
+            206 => "Partial Content",
+            304 => "Not Modified",
             400 => "Bad Request",
             404 => "Not Found",
             405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            413 => "Payload Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            431 => "Request Header Fields Too Large",
             501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
             505 => "HTTP Version Not Supported",
             507 => "Insufficient Storage (WebDAV; RFC 4918)",
             _ => "Internal Server Error"
@@ -47,71 +199,50 @@ impl HttpResponse {
     }
 }
 
-impl From<Vec<u8>> for HttpResponse {
-    fn from(value: Vec<u8>) -> Self {
-        let mut res = HttpResponse::default();
-
-        let mut buf_reader = BufReader::new(value.as_slice());
-        
-        // First line
-        let mut response_line = String::new();
-        let _ = buf_reader.read_line(&mut response_line);
-        let response_line: Vec<&str> = response_line.trim().split(" ").collect();
-        res.version = response_line[0].to_string();
-        res.status = response_line[1].parse().unwrap();
-        res.reason = response_line[2].to_string();
-
-
-        //Headers
-        loop {
-            let mut header_line = String::new();
-            let _ = buf_reader.read_line(&mut header_line);
-
-            // The headers and body are separated by an empty line (CRLF)
-            if header_line.len() == 2 {
-                break
-            }
+impl HttpResponse {
+    // Compressed bodies are decompressed, so the contents are always the
+    // actual representation, and the headers are updated to match it
+    pub fn set_received_contents(&mut self, content: Vec<u8>) {
+        let encoding = self.headers.get("Content-Encoding").and_then(|name| Encoding::from_name(name));
+
+        let content = match encoding.map(|encoding| compression::decompress(encoding, &content)) {
+            Some(Ok(decompressed)) => {
+                self.headers.remove("Content-Encoding");
+
+                if self.headers.contains("Content-Length") {
+                    self.headers.insert("Content-Length".to_string(), decompressed.len().to_string());
+                }
+
+                decompressed
+            },
+            _ => content,
+        };
 
-            // The key-value pair is delimited by a colon char
-            let header: Vec<&str> = header_line.split(":").collect();
-            res.headers.insert(header[0].to_string(), header[1].trim().to_string());
-        }
+        self.contents = content;
+    }
 
-        //Body
-        let Some(content_length) = res.headers.get("Content-Length") else {
-            return res;
-        };
+    // The whole message, as sent to the client
+    pub fn to_bytes(&self) -> Vec<u8> {
+        // A buffered response marked as chunked is sent as a single chunk
+        if self.is_chunked() {
+            let mut message = self.head().into_bytes();
 
-        let content_length = content_length.parse::<u64>().unwrap();
+            if !self.contents.is_empty() {
+                message.extend_from_slice(&encode_chunk(&self.contents));
+            }
 
-        // If the content is 0-length'd, we can stop the function
-        if content_length == 0 {
-            return res;
+            message.extend_from_slice(LAST_CHUNK);
+            return message;
         }
 
-        // We read the indicated amount of bytes from the stream
-        let mut content: Vec<u8> = vec![0u8; content_length.try_into().unwrap()];
-        let _ = buf_reader.read_exact(&mut content);
-        res.contents = String::from_utf8(content).unwrap();
-
-        res 
+        let mut message = self.framed_head(Framing::Exact).into_bytes();
+        message.extend_from_slice(&self.contents);
+        message
     }
 }
 
 impl fmt::Display for HttpResponse {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut headers = "".to_string();
-
-        for (header, value) in &self.headers {
-            headers = headers + &header + ": " + &value + "\r\n";
-        }
-
-        write!(f, "{} {} {}\r\n{}\r\n{}",
-            self.version,
-            self.status,
-            self.reason,
-            headers,
-            self.contents,
-        )
+        write!(f, "{}", String::from_utf8_lossy(&self.to_bytes()))
     }
 }
@@ -8,4 +8,6 @@ pub struct Slave {
     pub socket: SocketAddr,
     pub token: CancellationToken,
     pub is_active: Arc<Mutex<bool>>,
+    // Busy threads in the slave's last heartbeat
+    pub busy: Arc<Mutex<usize>>,
 }
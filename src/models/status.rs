@@ -10,7 +10,10 @@ pub struct Status {
     pub pid: u64,
     pub requests_handled: u128,
     pub threads: HashMap<u64, Thread>,
-    pub run_time: String
+    pub run_time: String,
+    // Slaves from older builds don't report it
+    #[serde(default)]
+    pub load: Load,
 }
 
 impl Status {
@@ -20,7 +23,9 @@ impl Status {
         let threads: HashMap<u64, Thread> = HashMap::new();
         let run_time = String::new();
 
-        Status { start_time, pid, requests_handled, threads, run_time }
+        let load = Load::default();
+
+        Status { start_time, pid, requests_handled, threads, run_time, load }
     }
 
     pub fn get_pid(&self) -> u64 {
@@ -49,6 +54,14 @@ impl Status {
         }
     }
 
+    pub fn busy_threads(&self) -> usize {
+        self.threads.values().filter(|thread| thread.busy).count()
+    }
+
+    pub fn update_load(&mut self, load: Load) {
+        self.load = load;
+    }
+
     pub fn status(&mut self) -> String {
         let start_time: DateTime<Utc> = self.start_time.into();
         let now: DateTime<Utc> = SystemTime::now().into();
@@ -96,3 +109,14 @@ pub struct Thread {
     pub busy: bool,
     pub command: String,
 }
+
+// Work the server is admitting and how much it can take at once
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Load {
+    pub connections: usize,
+    pub max_connections: usize,
+    pub in_flight: usize,
+    pub max_in_flight: usize,
+    // Connections and requests answered with 503
+    pub rejected: u64,
+}
@@ -66,3 +66,29 @@ pub fn remove_keys_from_redis(pattern: String) -> RedisResult<()> {
 
     Ok(())
 }
+
+// Stores the value only if the key doesn't exist yet. Returns whether it was
+// stored, so concurrent writers can tell which one got the key
+pub fn add_new_bytes_to_redis(key: String, value: &[u8]) -> RedisResult<bool> {
+    // We attempt to connect to the DB
+    let mut connection = connect_to_redis()?;
+    let redis = &mut connection;
+
+    redis.set_nx(key, value)
+}
+
+pub fn get_bytes_from_redis(key: String) -> RedisResult<Option<Vec<u8>>> {
+    // We attempt to connect to the DB
+    let mut connection = connect_to_redis()?;
+    let redis = &mut connection;
+
+    redis.get(key)
+}
+
+pub fn key_exists_in_redis(key: String) -> RedisResult<bool> {
+    // We attempt to connect to the DB
+    let mut connection = connect_to_redis()?;
+    let redis = &mut connection;
+
+    redis.exists(key)
+}
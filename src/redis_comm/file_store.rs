@@ -0,0 +1,28 @@
+use redis::RedisResult;
+
+use super::connection;
+
+/// Stores an uploaded file's content, unless one with the same name exists.
+/// Returns whether it was stored
+pub fn add_file(name: &str, content: &[u8]) -> RedisResult<bool> {
+    let key = format!("file:{}", name);
+    connection::add_new_bytes_to_redis(key, content)
+}
+
+/// Gets an uploaded file's content, if there's one with the given name
+pub fn get_file(name: &str) -> RedisResult<Option<Vec<u8>>> {
+    let key = format!("file:{}", name);
+    connection::get_bytes_from_redis(key)
+}
+
+/// Checks whether a file with the given name was uploaded
+pub fn has_file(name: &str) -> RedisResult<bool> {
+    let key = format!("file:{}", name);
+    connection::key_exists_in_redis(key)
+}
+
+/// Removes an uploaded file's content
+pub fn remove_file(name: &str) -> RedisResult<()> {
+    let key = format!("file:{}", name);
+    connection::remove_key_from_redis(key)
+}
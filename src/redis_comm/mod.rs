@@ -1,5 +1,6 @@
 pub mod connection;
 pub mod count_store;
+pub mod file_store;
 pub mod matrix_store;
 
 #[cfg(test)]
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::models::response::HttpResponse;
+use crate::models::status::Load;
+
+use super::limits::Limits;
+
+// Counts shared by every server in the process, reported through /status
+static CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static MAX_CONNECTIONS: AtomicUsize = AtomicUsize::new(0);
+static MAX_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static REJECTED: AtomicU64 = AtomicU64::new(0);
+
+// Connections over the cap being answered at once. Past it, they're reset
+const MAX_REJECTIONS: usize = 64;
+
+// Caps the connections and requests a server handles at once, so bursts are
+// answered with 503 instead of growing tasks and memory without bound
+#[derive(Debug, Clone)]
+pub struct Admission {
+    connections: Arc<Semaphore>,
+    requests: Arc<Semaphore>,
+    rejections: Arc<Semaphore>,
+}
+
+// Held while a connection is open or a request is answered. The slot is
+// freed when dropped
+#[derive(Debug)]
+pub struct Permit {
+    _permit: OwnedSemaphorePermit,
+    counter: &'static AtomicUsize,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Admission {
+    pub fn new(limits: &Limits) -> Admission {
+        MAX_CONNECTIONS.store(limits.max_connections, Ordering::Relaxed);
+        MAX_IN_FLIGHT.store(limits.max_in_flight, Ordering::Relaxed);
+
+        Admission {
+            connections: Arc::new(Semaphore::new(limits.max_connections)),
+            requests: Arc::new(Semaphore::new(limits.max_in_flight)),
+            rejections: Arc::new(Semaphore::new(MAX_REJECTIONS)),
+        }
+    }
+
+    // None if the server already has as many connections as it can take
+    pub fn connection(&self) -> Option<Permit> {
+        acquire(&self.connections, &CONNECTIONS)
+    }
+
+    // None if the server is already answering as many requests as it can
+    pub fn request(&self) -> Option<Permit> {
+        acquire(&self.requests, &IN_FLIGHT)
+    }
+
+    // None if too many connections over the cap are already being answered
+    pub fn rejection(&self) -> Option<OwnedSemaphorePermit> {
+        Arc::clone(&self.rejections).try_acquire_owned().ok()
+    }
+}
+
+// Answer for anything that wasn't admitted
+pub fn unavailable(retry_after: Duration) -> HttpResponse {
+    let mut res = HttpResponse::text(503, "Server is at capacity.\nTry again later.");
+    res.headers.insert("Retry-After".to_string(), retry_after.as_secs().to_string());
+    res
+}
+
+fn acquire(semaphore: &Arc<Semaphore>, counter: &'static AtomicUsize) -> Option<Permit> {
+    let Ok(permit) = Arc::clone(semaphore).try_acquire_owned() else {
+        REJECTED.fetch_add(1, Ordering::Relaxed);
+        return None;
+    };
+
+    counter.fetch_add(1, Ordering::Relaxed);
+    Some(Permit { _permit: permit, counter })
+}
+
+pub fn load() -> Load {
+    Load {
+        connections: CONNECTIONS.load(Ordering::Relaxed),
+        max_connections: MAX_CONNECTIONS.load(Ordering::Relaxed),
+        in_flight: IN_FLIGHT.load(Ordering::Relaxed),
+        max_in_flight: MAX_IN_FLIGHT.load(Ordering::Relaxed),
+        rejected: REJECTED.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,142 @@
+use std::io::{self, Read, Write};
+use flate2::Compression;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+
+use crate::models::headers::Headers;
+
+// Content codings we can compress responses with. HTTP's deflate is the zlib
+// format, not raw deflate
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "gzip" | "x-gzip" => Some(Encoding::Gzip),
+            "deflate" => Some(Encoding::Deflate),
+            _ => None,
+        }
+    }
+}
+
+// Picks the coding with the highest quality value in the Accept-Encoding
+// header. Ties favour gzip, and codings with q=0 are refused, even through *
+pub fn negotiate(headers: &Headers) -> Option<Encoding> {
+    let mut gzip: Option<f32> = None;
+    let mut deflate: Option<f32> = None;
+    let mut any: Option<f32> = None;
+
+    for value in headers.get_all("Accept-Encoding") {
+        for coding in value.split(",") {
+            let mut parts = coding.split(";");
+            let name = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+
+            // Invalid quality values are taken as the default one
+            let quality = parts
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+
+            match name.as_str() {
+                "gzip" | "x-gzip" => gzip = Some(quality),
+                "deflate" => deflate = Some(quality),
+                "*" => any = Some(quality),
+                _ => {},
+            }
+        }
+    }
+
+    let gzip = gzip.or(any).unwrap_or(0.0);
+    let deflate = deflate.or(any).unwrap_or(0.0);
+
+    if gzip > 0.0 && gzip >= deflate {
+        Some(Encoding::Gzip)
+    } else if deflate > 0.0 {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+pub fn compress(encoding: Encoding, data: &[u8]) -> io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(vec![], Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        },
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(data)?;
+            encoder.finish()
+        },
+    }
+}
+
+pub fn decompress(encoding: Encoding, data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut decompressed = vec![];
+
+    match encoding {
+        Encoding::Gzip => GzDecoder::new(data).read_to_end(&mut decompressed)?,
+        Encoding::Deflate => ZlibDecoder::new(data).read_to_end(&mut decompressed)?,
+    };
+
+    Ok(decompressed)
+}
+
+enum Encoder {
+    Gzip(GzEncoder<Vec<u8>>),
+    Deflate(ZlibEncoder<Vec<u8>>),
+}
+
+// Compresses a body produced in parts. Each part is flushed right away, so
+// the client doesn't wait for data the encoder would otherwise hold back
+pub struct StreamCompressor {
+    encoder: Encoder,
+}
+
+impl StreamCompressor {
+    pub fn new(encoding: Encoding) -> StreamCompressor {
+        let encoder = match encoding {
+            Encoding::Gzip => Encoder::Gzip(GzEncoder::new(vec![], Compression::default())),
+            Encoding::Deflate => Encoder::Deflate(ZlibEncoder::new(vec![], Compression::default())),
+        };
+
+        StreamCompressor { encoder }
+    }
+
+    // Returns the compressed bytes available so far
+    pub fn compress(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match &mut self.encoder {
+            Encoder::Gzip(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            },
+            Encoder::Deflate(encoder) => {
+                encoder.write_all(data)?;
+                encoder.flush()?;
+                Ok(std::mem::take(encoder.get_mut()))
+            },
+        }
+    }
+
+    // Returns the remaining bytes, which close the compressed body
+    pub fn finish(self) -> io::Result<Vec<u8>> {
+        match self.encoder {
+            Encoder::Gzip(encoder) => encoder.finish(),
+            Encoder::Deflate(encoder) => encoder.finish(),
+        }
+    }
+}
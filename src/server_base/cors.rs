@@ -0,0 +1,150 @@
+use std::env;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use crate::models::request::HttpRequest;
+use crate::models::response::{HttpResponse, Response};
+
+const DEFAULT_METHODS: &str = "GET, HEAD, POST, DELETE, OPTIONS";
+const DEFAULT_HEADERS: &str = "Content-Type";
+const DEFAULT_MAX_AGE: Duration = Duration::from_secs(600);
+
+// Read once, as every connection uses the same policy
+static CORS: LazyLock<Option<Cors>> = LazyLock::new(Cors::from_env);
+
+// Which browser origins can call the server and how. Requests from other
+// origins are answered as usual, without the headers browsers need to read
+// the response
+#[derive(Debug, Clone)]
+pub struct Cors {
+    // A single * allows any origin
+    pub origins: Vec<String>,
+    pub methods: Vec<String>,
+    // A single * allows any header
+    pub headers: Vec<String>,
+    // Time browsers can cache a preflight's answer
+    pub max_age: Duration,
+    // Whether requests can carry cookies or authorization
+    pub credentials: bool,
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Cors {
+            origins: vec![],
+            methods: list(DEFAULT_METHODS),
+            headers: list(DEFAULT_HEADERS),
+            max_age: DEFAULT_MAX_AGE,
+            credentials: false,
+        }
+    }
+}
+
+impl Cors {
+    // CORS is off unless CORS_ALLOWED_ORIGINS is set. The other vars use the
+    // default if missing or if they can't be parsed
+    pub fn from_env() -> Option<Cors> {
+        let origins = list(&env::var("CORS_ALLOWED_ORIGINS").ok()?);
+
+        if origins.is_empty() {
+            return None;
+        }
+
+        let default = Cors::default();
+
+        Some(Cors {
+            origins,
+            methods: env::var("CORS_ALLOWED_METHODS").map(|methods| list(&methods)).unwrap_or(default.methods),
+            headers: env::var("CORS_ALLOWED_HEADERS").map(|headers| list(&headers)).unwrap_or(default.headers),
+            max_age: env::var("CORS_MAX_AGE").ok()
+                .and_then(|seconds| seconds.parse::<u64>().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(default.max_age),
+            credentials: env::var("CORS_ALLOW_CREDENTIALS").is_ok_and(|credentials| credentials.eq_ignore_ascii_case("true")),
+        })
+    }
+
+    // Answers a preflight from an allowed origin, which browsers send before
+    // any request that isn't a simple GET or POST
+    pub fn preflight(&self, req: &HttpRequest) -> Option<HttpResponse> {
+        if req.method != "OPTIONS" || !req.headers.contains("Access-Control-Request-Method") {
+            return None;
+        }
+
+        let origin = self.allowed_origin(req.headers.get("Origin")?)?;
+        let mut res = HttpResponse::basic(204);
+
+        // Credentials can't be used with a wildcard, so the requested headers
+        // are echoed instead
+        let headers = match (is_wildcard(&self.headers), self.credentials) {
+            (true, false) => "*".to_string(),
+            (true, true) => req.headers.get("Access-Control-Request-Headers").cloned().unwrap_or_default(),
+            (false, _) => self.headers.join(", "),
+        };
+
+        res.headers.insert("Access-Control-Allow-Origin".to_string(), origin);
+        res.headers.insert("Access-Control-Allow-Methods".to_string(), self.methods.join(", "));
+
+        if !headers.is_empty() {
+            res.headers.insert("Access-Control-Allow-Headers".to_string(), headers);
+        }
+
+        res.headers.insert("Access-Control-Max-Age".to_string(), self.max_age.as_secs().to_string());
+
+        if self.credentials {
+            res.headers.insert("Access-Control-Allow-Credentials".to_string(), "true".to_string());
+        }
+
+        res.headers.add_token("Vary", "Origin");
+        res.headers.add_token("Vary", "Access-Control-Request-Method");
+        res.headers.add_token("Vary", "Access-Control-Request-Headers");
+        Some(res)
+    }
+
+    // Lets the browser read a response sent to an allowed origin
+    pub fn decorate(&self, origin: Option<&str>, res: &mut Response) {
+        let res = res.head_mut();
+
+        // The answer depends on the origin unless every one gets a *
+        if !is_wildcard(&self.origins) || self.credentials {
+            res.headers.add_token("Vary", "Origin");
+        }
+
+        let Some(origin) = origin.and_then(|origin| self.allowed_origin(origin)) else {
+            return;
+        };
+
+        res.headers.insert("Access-Control-Allow-Origin".to_string(), origin);
+
+        if self.credentials {
+            res.headers.insert("Access-Control-Allow-Credentials".to_string(), "true".to_string());
+        }
+    }
+
+    // Value of Access-Control-Allow-Origin for the given origin, if allowed.
+    // Credentials can't be used with a wildcard, so the origin is echoed
+    fn allowed_origin(&self, origin: &str) -> Option<String> {
+        if is_wildcard(&self.origins) {
+            return Some(if self.credentials { origin.to_string() } else { "*".to_string() });
+        }
+
+        self.origins.iter()
+            .find(|allowed| allowed.trim_end_matches("/") == origin)
+            .map(|_| origin.to_string())
+    }
+}
+
+pub fn policy() -> Option<&'static Cors> {
+    CORS.as_ref()
+}
+
+fn is_wildcard(values: &[String]) -> bool {
+    values.iter().any(|value| value == "*")
+}
+
+fn list(value: &str) -> Vec<String> {
+    value.split(",")
+        .map(|item| item.trim().to_string())
+        .filter(|item| !item.is_empty())
+        .collect()
+}
@@ -0,0 +1,92 @@
+use std::env;
+use std::time::Duration;
+
+// Bounds applied to connections, so a slow or malicious client can't pin
+// memory or tasks. Each value can be overridden with an env var
+#[derive(Debug, Clone)]
+pub struct Limits {
+    // Bytes of the request line, answered with 414
+    pub max_request_line: usize,
+    // Amount of header lines, answered with 431
+    pub max_headers: usize,
+    // Bytes of all header lines together, answered with 431
+    pub max_headers_size: usize,
+    // Bytes of the decoded body, answered with 413
+    pub max_body_size: usize,
+    // Time to receive the request line and headers, answered with 408
+    pub headers_timeout: Duration,
+    // Time to receive the body once the headers are in, answered with 408
+    pub body_timeout: Duration,
+    // Time a persistent connection can stay idle between requests
+    pub keep_alive_timeout: Duration,
+    // Time in-flight requests get to finish once shutdown starts
+    pub shutdown_grace: Duration,
+    // Bytes a body needs to be compressed, smaller ones aren't worth it
+    pub compression_min_size: usize,
+    // Connections open at once, new ones are answered with 503
+    pub max_connections: usize,
+    // Requests being answered at once, new ones are answered with 503
+    pub max_in_flight: usize,
+    // Time clients are told to wait after a 503
+    pub retry_after: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_request_line: 8 * 1024,
+            max_headers: 100,
+            max_headers_size: 16 * 1024,
+            max_body_size: 10 * 1024 * 1024,
+            headers_timeout: Duration::from_secs(10),
+            body_timeout: Duration::from_secs(30),
+            keep_alive_timeout: Duration::from_secs(5),
+            shutdown_grace: Duration::from_secs(30),
+            compression_min_size: 1024,
+            max_connections: 1024,
+            max_in_flight: 256,
+            retry_after: Duration::from_secs(5),
+        }
+    }
+}
+
+impl Limits {
+    // Slaves run their handlers on the runtime's threads, so they can take
+    // much less work at once than the master, which mostly waits on them
+    pub fn for_role(role: &str) -> Limits {
+        if role == "MASTER" {
+            return Limits::default();
+        }
+
+        Limits { max_connections: 256, max_in_flight: 32, ..Default::default() }
+    }
+
+    // We use the role's default for any var that is missing or can't be parsed
+    pub fn from_env(role: &str) -> Limits {
+        let default = Limits::for_role(role);
+
+        Limits {
+            max_request_line: read_var("MAX_REQUEST_LINE").unwrap_or(default.max_request_line),
+            max_headers: read_var("MAX_HEADERS").unwrap_or(default.max_headers),
+            max_headers_size: read_var("MAX_HEADERS_SIZE").unwrap_or(default.max_headers_size),
+            max_body_size: read_var("MAX_BODY_SIZE").unwrap_or(default.max_body_size),
+            headers_timeout: read_secs("HEADERS_TIMEOUT").unwrap_or(default.headers_timeout),
+            body_timeout: read_secs("BODY_TIMEOUT").unwrap_or(default.body_timeout),
+            keep_alive_timeout: read_secs("KEEP_ALIVE_TIMEOUT").unwrap_or(default.keep_alive_timeout),
+            shutdown_grace: read_secs("SHUTDOWN_GRACE_PERIOD").unwrap_or(default.shutdown_grace),
+            compression_min_size: read_var("COMPRESSION_MIN_SIZE").unwrap_or(default.compression_min_size),
+            max_connections: read_var("MAX_CONNECTIONS").filter(|max| *max > 0).unwrap_or(default.max_connections),
+            max_in_flight: read_var("MAX_IN_FLIGHT").filter(|max| *max > 0).unwrap_or(default.max_in_flight),
+            retry_after: read_secs("RETRY_AFTER").unwrap_or(default.retry_after),
+        }
+    }
+}
+
+pub(crate) fn read_var(key: &str) -> Option<usize> {
+    env::var(key).ok()?.parse::<usize>().ok()
+}
+
+pub(crate) fn read_secs(key: &str) -> Option<Duration> {
+    let seconds = env::var(key).ok()?.parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds))
+}
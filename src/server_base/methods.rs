@@ -0,0 +1,34 @@
+use crate::models::response::HttpResponse;
+
+// Value of the Allow header for a route. Every route answers OPTIONS and,
+// when it allows GET, HEAD too
+pub fn allow(methods: &[&str]) -> String {
+    let mut allowed = methods.to_vec();
+
+    if allowed.contains(&"GET") && !allowed.contains(&"HEAD") {
+        allowed.push("HEAD");
+    }
+
+    if !allowed.contains(&"OPTIONS") {
+        allowed.push("OPTIONS");
+    }
+
+    allowed.join(", ")
+}
+
+// Answers the requests that shouldn't reach the route: OPTIONS ones and the
+// ones with a method it doesn't allow. HEAD requests arrive here as GET ones,
+// as the server only drops the body of their response
+pub fn check(method: &str, methods: &[&str]) -> Option<HttpResponse> {
+    let status = if method == "OPTIONS" {
+        204
+    } else if methods.contains(&method) {
+        return None;
+    } else {
+        405
+    };
+
+    let mut res = HttpResponse::basic(status);
+    res.headers.insert("Allow".to_string(), allow(methods));
+    Some(res)
+}
@@ -1,2 +1,1393 @@
+pub mod admission;
+pub mod compression;
+pub mod cors;
+pub mod limits;
+pub mod methods;
+pub mod multipart;
 pub mod parser;
+pub mod request_id;
+pub mod router;
 pub mod server;
+pub mod shutdown;
+pub mod static_files;
+pub mod tls;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+    use proptest::prelude::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_util::sync::CancellationToken;
+
+    use crate::client::{client, pool};
+    use crate::client::tls::ClientTls;
+    use crate::models::request::Body;
+    use crate::models::response::{self, HttpResponse, Response};
+    use crate::client::tests::parse_response;
+    use super::*;
+
+    type ParseResult = Result<crate::models::request::HttpRequest, Box<dyn std::error::Error + Send + Sync>>;
+
+    async fn parse_raw(message: &'static str) -> ParseResult {
+        parse_raw_limited(message, limits::Limits::default()).await
+    }
+
+    // Sends the raw message through a local socket and parses the other end.
+    // The client side stays open for a while, so timeouts can be tested
+    async fn parse_raw_limited(message: &'static str, limits: limits::Limits) -> ParseResult {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let mut stream = TcpStream::connect(address).await.unwrap();
+            stream.write_all(message.as_bytes()).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        });
+
+        let (mut stream, _) = listener.accept().await.unwrap();
+        parser::parse(&mut stream, &mut parser::RequestParser::new(limits)).await
+    }
+
+    async fn connect_slave() -> TcpStream {
+        connect_slave_limited(limits::Limits::default()).await
+    }
+
+    // Starts a slave connection handler and returns the client side
+    async fn connect_slave_limited(limits: limits::Limits) -> TcpStream {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, remote) = listener.accept().await.unwrap();
+            let admission = admission::Admission::new(&limits);
+            server::handle_requests(stream, remote, "SLAVE".to_string(), Arc::new(limits), admission, CancellationToken::new()).await;
+        });
+
+        TcpStream::connect(address).await.unwrap()
+    }
+
+    #[test]
+    fn urlencoded_decode_success() {
+        let res = parser::parse_urlencoded("text=hello%20world&name=a+b").unwrap();
+        assert_eq!(res[0], ("text".to_string(), "hello world".to_string()));
+        assert_eq!(res[1], ("name".to_string(), "a b".to_string()));
+    }
+
+    #[test]
+    fn urlencoded_non_ascii_success() {
+        let res = parser::parse_urlencoded("text=canci%C3%B3n+espa%C3%B1ola").unwrap();
+        assert_eq!(res[0].1, "canción española");
+    }
+
+    #[test]
+    fn urlencoded_repeated_keys_success() {
+        let res = parser::parse_urlencoded("a=1&a=2&&b=x=y").unwrap();
+        assert_eq!(res.len(), 3);
+        assert_eq!(res[0], ("a".to_string(), "1".to_string()));
+        assert_eq!(res[1], ("a".to_string(), "2".to_string()));
+        assert_eq!(res[2], ("b".to_string(), "x=y".to_string()));
+    }
+
+    #[test]
+    fn urlencoded_valueless_key_success() {
+        let res = parser::parse_urlencoded("flag&text=").unwrap();
+        assert_eq!(res[0], ("flag".to_string(), "".to_string()));
+        assert_eq!(res[1], ("text".to_string(), "".to_string()));
+    }
+
+    #[test]
+    fn urlencoded_malformed_error() {
+        assert!(parser::parse_urlencoded("text=%2").is_err());
+        assert!(parser::parse_urlencoded("text=%zz").is_err());
+        assert!(parser::parse_urlencoded("text=%+1").is_err());
+        assert!(parser::parse_urlencoded("=value").is_err());
+        // Lone continuation byte, not valid UTF-8
+        assert!(parser::parse_urlencoded("text=%80").is_err());
+    }
+
+    #[test]
+    fn percent_encode_roundtrip() {
+        let text = "año 100% a+b&c=d/é";
+        let encoded = parser::percent_encode(text);
+        assert!(encoded.is_ascii());
+        assert_eq!(parser::percent_decode(&encoded, false).unwrap(), text);
+    }
+
+    #[test]
+    fn percent_decode_plus() {
+        assert_eq!(parser::percent_decode("a+b", false).unwrap(), "a+b");
+        assert_eq!(parser::percent_decode("a+b", true).unwrap(), "a b");
+    }
+
+    #[tokio::test]
+    async fn urlencoded_body_success() {
+        let req = parse_raw("POST /createfile?name=a.txt HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded; charset=UTF-8\r\nContent-Length: 29\r\n\r\ncontent=hola+mundo&repeat=%32").await.unwrap();
+
+        let Body::URLdec(pairs) = &req.body else {
+            panic!("Body wasn't parsed as urlencoded");
+        };
+
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(req.param("name").unwrap(), "a.txt");
+        assert_eq!(req.param("content").unwrap(), "hola mundo");
+        assert_eq!(req.param("repeat").unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn urlencoded_body_display() {
+        let req = parse_raw("POST /createfile HTTP/1.1\r\nContent-Type: application/x-www-form-urlencoded\r\nContent-Length: 18\r\n\r\ncontent=hola+mundo").await.unwrap();
+        let message = format!("{}", req);
+
+        assert!(message.starts_with("POST /createfile HTTP/1.1\r\n"));
+        assert!(message.contains("Content-Length: 20\r\n"));
+        assert!(message.ends_with("\r\n\r\ncontent=hola%20mundo"));
+    }
+
+    #[tokio::test]
+    async fn chunked_body_success() {
+        let req = parse_raw("GET /matrixmult HTTP/1.1\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n4;ext=1\r\n{\"a\"\r\n3\r\n: 1\r\nA\r\n, \"b\": 22}\r\n0\r\nX-Trailer: yes\r\n\r\n").await.unwrap();
+
+        let Body::JSON(content) = req.body else {
+            panic!("Body wasn't parsed as JSON");
+        };
+
+        assert_eq!(content, "{\"a\": 1, \"b\": 22}");
+    }
+
+    #[tokio::test]
+    async fn chunked_body_size_error() {
+        let res = parse_raw("GET /matrixmult HTTP/1.1\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n{}\r\n0\r\n\r\n").await;
+        assert!(res.unwrap_err().is::<crate::errors::parse::ParseUriError>());
+    }
+
+    #[tokio::test]
+    async fn transfer_encoding_unsupported_error() {
+        let res = parse_raw("GET /matrixmult HTTP/1.1\r\nContent-Type: application/json\r\nTransfer-Encoding: gzip\r\n\r\n").await;
+        assert!(res.unwrap_err().is::<crate::errors::implement::ImplementationError>());
+    }
+
+    #[test]
+    fn chunked_response_roundtrip() {
+        let mut res = HttpResponse::basic(200);
+        res.headers.insert("Transfer-Encoding".to_string(), "chunked".to_string());
+        res.contents = b"hello world".to_vec();
+
+        let message = format!("{}", res);
+        assert!(message.ends_with("\r\n\r\nB\r\nhello world\r\n0\r\n\r\n"));
+
+        let parsed = parse_response(message.as_bytes(), false, 1024).unwrap();
+        assert_eq!(parsed.contents_str(), "hello world");
+    }
+
+    #[tokio::test]
+    async fn binary_body_success() {
+        let req = parse_raw("POST /upload HTTP/1.1\r\nContent-Type: application/octet-stream\r\nContent-Length: 4\r\n\r\n\u{0}\u{7f}ab").await.unwrap();
+
+        let Body::Binary(content) = &req.body else {
+            panic!("Body wasn't kept as bytes");
+        };
+
+        assert_eq!(content, b"\0\x7fab");
+        assert!(req.to_bytes().ends_with(b"Content-Length: 4\r\n\r\n\0\x7fab"));
+    }
+
+    #[tokio::test]
+    async fn edited_params_success() {
+        let mut req = parse_raw("GET /reverse?text=abc&name=a&name=b HTTP/1.1\r\n\r\n").await.unwrap();
+        req.params.insert("text".to_string(), "xyz".to_string());
+        req.params.insert("extra".to_string(), "1".to_string());
+
+        // Edited params are forwarded, repeated ones that weren't keep every value
+        let message = String::from_utf8(req.to_bytes()).unwrap();
+        let line = message.lines().next().unwrap();
+        assert!(line.contains("text=xyz"));
+        assert!(!line.contains("text=abc"));
+        assert!(line.contains("extra=1"));
+        assert!(line.contains("name=a&name=b"));
+    }
+
+    #[test]
+    fn binary_response_roundtrip() {
+        let contents = vec![0xff, 0x00, 0xc3, 0x28, b'\n'];
+        let res = HttpResponse::binary(200, contents.clone(), "image/png");
+
+        let parsed = parse_response(&res.to_bytes(), false, 1024).unwrap();
+        assert_eq!(parsed.contents, contents);
+        assert_eq!(parsed.headers.get("Content-Type").unwrap(), "image/png");
+        assert_eq!(parsed.headers.get("Content-Length").unwrap(), "5");
+    }
+
+    #[test]
+    fn typed_responses_success() {
+        let res = HttpResponse::text(400, "bad");
+        assert_eq!(res.status, 400);
+        assert_eq!(res.headers.get("Content-Type").unwrap(), response::DEFAULT_CONTENT_TYPE);
+        assert_eq!(res.contents_str(), "bad");
+
+        let res = HttpResponse::json(200, "{}");
+        assert_eq!(res.headers.get("Content-Type").unwrap(), "application/json");
+        assert_eq!(res.contents, b"{}");
+    }
+
+    #[test]
+    fn encode_chunk_success() {
+        assert_eq!(response::encode_chunk(b"0123456789abcdef"), b"10\r\n0123456789abcdef\r\n");
+    }
+
+    #[tokio::test]
+    async fn keep_alive_pipelined_success() {
+        let mut stream = connect_slave().await;
+
+        // Both requests are sent before reading any response
+        stream.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\nGET /toupper?text=abc HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        // The server closes the connection after the 2nd response
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).await.unwrap();
+
+        let first = responses.find("cba").unwrap();
+        let second = responses.find("ABC").unwrap();
+        assert!(first < second);
+        assert_eq!(responses.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(responses.contains("Connection: close"));
+    }
+
+    #[tokio::test]
+    async fn keep_alive_sequential_success() {
+        let mut stream = connect_slave().await;
+
+        for text in ["abc", "def"] {
+            let req = format!("GET /reverse?text={} HTTP/1.1\r\n\r\n", text);
+            stream.write_all(req.as_bytes()).await.unwrap();
+
+            // Content-Length: 3 lets us know where the response ends
+            let mut buf = vec![0u8; 1024];
+            let read = stream.read(&mut buf).await.unwrap();
+            let res = parse_response(&buf[..read], false, 1024).unwrap();
+
+            assert_eq!(res.status, 200);
+            assert_eq!(res.contents_str(), text.chars().rev().collect::<String>());
+        }
+    }
+
+    #[tokio::test]
+    async fn bad_request_closes_error() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"GET /reverse?text=%zz HTTP/1.1\r\n\r\nGET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).await.unwrap();
+
+        assert!(responses.starts_with("HTTP/1.1 400 Bad Request"));
+        assert!(!responses.contains("cba"));
+    }
+
+    #[tokio::test]
+    async fn headers_case_insensitive_success() {
+        let req = parse_raw("POST /createfile HTTP/1.1\r\nHost: a:7878\r\ncontent-type: application/x-www-form-urlencoded\r\ncontent-length: 7\r\nX-Tag: a\r\nX-Tag: b\r\n\r\nname=ab").await.unwrap();
+
+        assert_eq!(req.headers.get("host").unwrap(), "a:7878");
+        assert_eq!(req.headers.get_all("x-tag"), vec!["a", "b"]);
+        assert_eq!(req.param("name").unwrap(), "ab");
+    }
+
+    #[test]
+    fn response_headers_parse() {
+        let res = parse_response(b"HTTP/1.1 200 OK\r\nlocation: http://a:7878/x\r\ncontent-length: 2\r\n\r\nok", false, 1024).unwrap();
+
+        assert_eq!(res.headers.get("Location").unwrap(), "http://a:7878/x");
+        assert_eq!(res.contents_str(), "ok");
+    }
+
+    #[test]
+    fn standard_headers_success() {
+        let res = format!("{}", HttpResponse::new("HTTP/1.1".to_string(), 200, crate::models::headers::Headers::new(), "abc".to_string()));
+        let parsed = parse_response(res.as_bytes(), false, 1024).unwrap();
+
+        assert!(res.ends_with("\r\n\r\nabc"));
+        assert_eq!(parsed.headers.get("Content-Length").unwrap(), "3");
+        assert_eq!(parsed.headers.get("Content-Type").unwrap(), response::DEFAULT_CONTENT_TYPE);
+        assert_eq!(parsed.headers.get("Server").unwrap(), response::SERVER);
+        assert!(parsed.headers.get("Date").unwrap().ends_with(" GMT"));
+
+        // Responses without a body don't get framing nor a type
+        let res = parse_response(format!("{}", HttpResponse::basic(204)).as_bytes(), false, 1024).unwrap();
+        assert!(res.headers.contains("Date"));
+        assert!(!res.headers.contains("Content-Length"));
+        assert!(!res.headers.contains("Content-Type"));
+
+        let res = parse_response(format!("{}", HttpResponse::basic(404)).as_bytes(), false, 1024).unwrap();
+        assert_eq!(res.headers.get("Content-Length").unwrap(), "0");
+        assert!(!res.headers.contains("Content-Type"));
+    }
+
+    #[test]
+    fn standard_headers_override_success() {
+        let mut headers = crate::models::headers::Headers::new();
+        headers.insert("Content-Type".to_string(), "application/json".to_string());
+        headers.insert("Server".to_string(), "custom".to_string());
+        headers.insert("Date".to_string(), "Thu, 01 Jan 1970 00:00:00 GMT".to_string());
+        headers.insert("Content-Length".to_string(), "10".to_string());
+        let res = HttpResponse::new("HTTP/1.1".to_string(), 200, headers, "{}".to_string());
+
+        let parsed = parse_response(format!("{}", res).as_bytes(), false, 1024).unwrap();
+        assert_eq!(parsed.headers.get("Content-Type").unwrap(), "application/json");
+        assert_eq!(parsed.headers.get("Server").unwrap(), "custom");
+        assert_eq!(parsed.headers.get("Date").unwrap(), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(parsed.headers.get_all("Content-Length"), vec!["2"]);
+
+        // Without the body, the declared length is kept
+        assert!(res.head().contains("Content-Length: 10\r\n"));
+        assert!(!res.head_until_close().contains("Content-Length"));
+    }
+
+    #[tokio::test]
+    async fn standard_headers_sent_success() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"GET /timestamp HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let res = parse_response(response.as_bytes(), false, 1024).unwrap();
+
+        assert_eq!(res.status, 200);
+        assert!(res.headers.contains("Date"));
+        assert_eq!(res.headers.get("Server").unwrap(), response::SERVER);
+        assert_eq!(res.headers.get("Content-Length").unwrap(), &res.contents.len().to_string());
+    }
+
+    #[tokio::test]
+    async fn request_line_limit_error() {
+        let limits = limits::Limits { max_request_line: 32, ..Default::default() };
+
+        let res = parse_raw_limited("GET /reverse?text=aaaaaaaaaaaaaaaaaaaaaaaa HTTP/1.1\r\n\r\n", limits).await;
+        assert!(res.unwrap_err().is::<crate::errors::limits::UriTooLongError>());
+    }
+
+    #[tokio::test]
+    async fn headers_count_limit_error() {
+        let limits = limits::Limits { max_headers: 2, ..Default::default() };
+
+        let res = parse_raw_limited("GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n", limits).await;
+        assert!(res.unwrap_err().is::<crate::errors::limits::HeadersTooLargeError>());
+    }
+
+    #[tokio::test]
+    async fn headers_size_limit_error() {
+        let limits = limits::Limits { max_headers_size: 16, ..Default::default() };
+
+        let res = parse_raw_limited("GET / HTTP/1.1\r\nA: 1\r\nB: 0123456789\r\n\r\n", limits).await;
+        assert!(res.unwrap_err().is::<crate::errors::limits::HeadersTooLargeError>());
+    }
+
+    #[tokio::test]
+    async fn body_limit_error() {
+        let limits = limits::Limits { max_body_size: 4, ..Default::default() };
+
+        let res = parse_raw_limited("POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 5\r\n\r\n[1,2]", limits.clone()).await;
+        assert!(res.unwrap_err().is::<crate::errors::limits::PayloadTooLargeError>());
+
+        let res = parse_raw_limited("POST / HTTP/1.1\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n3\r\n[1,\r\n2\r\n2]\r\n0\r\n\r\n", limits).await;
+        assert!(res.unwrap_err().is::<crate::errors::limits::PayloadTooLargeError>());
+    }
+
+    #[tokio::test]
+    async fn headers_timeout_error() {
+        let limits = limits::Limits { headers_timeout: Duration::from_millis(100), ..Default::default() };
+
+        // The headers never end, as the empty line is missing
+        let res = parse_raw_limited("GET / HTTP/1.1\r\nA: 1\r\n", limits).await;
+        assert!(res.unwrap_err().is::<crate::errors::limits::RequestTimeoutError>());
+    }
+
+    #[tokio::test]
+    async fn body_timeout_error() {
+        let limits = limits::Limits { body_timeout: Duration::from_millis(100), ..Default::default() };
+
+        let res = parse_raw_limited("POST / HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: 5\r\n\r\n[1", limits).await;
+        assert!(res.unwrap_err().is::<crate::errors::limits::RequestTimeoutError>());
+    }
+
+    #[tokio::test]
+    async fn limits_status_success() {
+        let limits = limits::Limits { max_request_line: 32, ..Default::default() };
+
+        let mut stream = connect_slave_limited(limits).await;
+        stream.write_all(b"GET /reverse?text=aaaaaaaaaaaaaaaaaaaaaaaa HTTP/1.1\r\n\r\n").await.unwrap();
+
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).await.unwrap();
+
+        assert!(responses.starts_with("HTTP/1.1 414 URI Too Long"));
+    }
+
+    #[tokio::test]
+    async fn http_1_0_success() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"GET /reverse?text=abc HTTP/1.0\r\n\r\n").await.unwrap();
+
+        // Without keep-alive, the server closes the connection after answering
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).await.unwrap();
+
+        assert!(responses.starts_with("HTTP/1.0 200 OK\r\n"));
+        assert!(responses.contains("Connection: close\r\n"));
+        assert!(responses.ends_with("cba"));
+    }
+
+    #[tokio::test]
+    async fn http_1_0_keep_alive_success() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"GET /reverse?text=abc HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET /reverse?text=def HTTP/1.0\r\n\r\n").await.unwrap();
+
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).await.unwrap();
+
+        assert_eq!(responses.matches("HTTP/1.0 200 OK").count(), 2);
+        assert!(responses.contains("Connection: keep-alive\r\n"));
+        assert!(responses.ends_with("fed"));
+    }
+
+    #[tokio::test]
+    async fn unknown_version_error() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"GET /reverse?text=abc HTTP/2.0\r\n\r\n").await.unwrap();
+
+        let mut responses = String::new();
+        stream.read_to_string(&mut responses).await.unwrap();
+
+        assert!(responses.starts_with("HTTP/1.1 505 HTTP Version Not Supported"));
+    }
+
+    #[test]
+    fn allow_header_success() {
+        assert_eq!(methods::allow(&["GET"]), "GET, HEAD, OPTIONS");
+        assert_eq!(methods::allow(&["POST"]), "POST, OPTIONS");
+        assert!(methods::check("GET", &["GET"]).is_none());
+    }
+
+    #[tokio::test]
+    async fn options_success() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"OPTIONS /reverse HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+        assert!(!response.contains("Content-Length"));
+    }
+
+    #[tokio::test]
+    async fn options_asterisk_success() {
+        let req = parse_raw("OPTIONS * HTTP/1.1\r\n\r\n").await.unwrap();
+        assert!(req.is_asterisk());
+        assert!(req.to_bytes().starts_with(b"OPTIONS * HTTP/1.1\r\n"));
+
+        // Only OPTIONS requests can target the whole server
+        let res = parse_raw("GET * HTTP/1.1\r\n\r\n").await;
+        assert!(res.unwrap_err().is::<crate::errors::parse::ParseUriError>());
+
+        let mut stream = connect_slave().await;
+        stream.write_all(b"OPTIONS * HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(response.contains("Allow: POST, DELETE, GET, HEAD, OPTIONS\r\n"));
+    }
+
+    #[tokio::test]
+    async fn method_not_allowed_error() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"POST /reverse?text=abc HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(response.contains("Allow: GET, HEAD, OPTIONS\r\n"));
+    }
+
+    #[tokio::test]
+    async fn head_success() {
+        let mut stream = connect_slave().await;
+
+        // The second response must start right after the first head, as it
+        // has no body
+        stream.write_all(b"HEAD /reverse?text=abc HTTP/1.1\r\n\r\nGET /reverse?text=def HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let (head, rest) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(head.contains("Content-Length: 3"));
+        assert!(rest.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(rest.ends_with("fed"));
+        assert!(!response.contains("cba"));
+    }
+
+    #[tokio::test]
+    async fn head_post_only_error() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"HEAD /createfile HTTP/1.1\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+        assert!(response.contains("Allow: POST, OPTIONS\r\n"));
+    }
+
+    fn accept_encoding(value: &str) -> Option<compression::Encoding> {
+        let mut headers = crate::models::headers::Headers::new();
+        headers.append("Accept-Encoding".to_string(), value.to_string());
+        compression::negotiate(&headers)
+    }
+
+    #[test]
+    fn negotiate_encoding_success() {
+        use compression::Encoding;
+
+        assert_eq!(accept_encoding("gzip, deflate, br"), Some(Encoding::Gzip));
+        assert_eq!(accept_encoding("deflate"), Some(Encoding::Deflate));
+        assert_eq!(accept_encoding("gzip;q=0.5, deflate;q=0.8"), Some(Encoding::Deflate));
+        assert_eq!(accept_encoding("*"), Some(Encoding::Gzip));
+        assert_eq!(accept_encoding("*;q=0.5, gzip;q=0"), Some(Encoding::Deflate));
+        assert_eq!(accept_encoding("identity"), None);
+        assert_eq!(accept_encoding("br, gzip;q=0"), None);
+        assert_eq!(compression::negotiate(&crate::models::headers::Headers::new()), None);
+    }
+
+    #[test]
+    fn compression_roundtrip_success() {
+        let text = "matrix ".repeat(500);
+
+        for encoding in [compression::Encoding::Gzip, compression::Encoding::Deflate] {
+            let compressed = compression::compress(encoding, text.as_bytes()).unwrap();
+            assert!(compressed.len() < text.len());
+            assert_eq!(compression::decompress(encoding, &compressed).unwrap(), text.as_bytes());
+
+            // A stream compressed in parts decompresses to the whole data
+            let mut compressor = compression::StreamCompressor::new(encoding);
+            let mut streamed = compressor.compress(&text.as_bytes()[..1000]).unwrap();
+            streamed.extend(compressor.compress(&text.as_bytes()[1000..]).unwrap());
+            streamed.extend(compressor.finish().unwrap());
+            assert_eq!(compression::decompress(encoding, &streamed).unwrap(), text.as_bytes());
+        }
+    }
+
+    #[tokio::test]
+    async fn compressed_response_success() {
+        let mut stream = connect_slave().await;
+        let text = "a".repeat(2000);
+        let req = format!("GET /toupper?text={} HTTP/1.1\r\nAccept-Encoding: gzip\r\nConnection: close\r\n\r\n", text);
+        stream.write_all(req.as_bytes()).await.unwrap();
+
+        let mut response = vec![];
+        stream.read_to_end(&mut response).await.unwrap();
+        let head = String::from_utf8_lossy(&response).to_string();
+
+        assert!(head.contains("Content-Encoding: gzip\r\n"));
+        assert!(head.contains("Vary: Accept-Encoding\r\n"));
+        assert!(response.len() < text.len());
+
+        // The client decompresses it
+        let res = parse_response(&response, false, 1024).unwrap();
+        assert_eq!(res.contents_str(), text.to_uppercase());
+        assert!(!res.headers.contains("Content-Encoding"));
+        assert_eq!(res.headers.get("Content-Length").unwrap(), "2000");
+    }
+
+    #[tokio::test]
+    async fn small_response_uncompressed_success() {
+        let mut stream = connect_slave().await;
+        stream.write_all(b"GET /reverse?text=abc HTTP/1.1\r\nAccept-Encoding: gzip, deflate\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(!response.contains("Content-Encoding"));
+        assert!(!response.contains("Vary"));
+        assert!(response.ends_with("cba"));
+    }
+
+    #[tokio::test]
+    async fn uncompressed_without_accept_encoding_success() {
+        let mut stream = connect_slave().await;
+        let text = "a".repeat(2000);
+        let req = format!("GET /toupper?text={} HTTP/1.1\r\nConnection: close\r\n\r\n", text);
+        stream.write_all(req.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        // It could have been compressed, so caches are still told
+        assert!(!response.contains("Content-Encoding"));
+        assert!(response.contains("Vary: Accept-Encoding\r\n"));
+        assert!(response.ends_with(&text.to_uppercase()));
+    }
+
+    // Starts a slave server that shuts down once the returned token is
+    // cancelled, the handle finishes when the server is stopped
+    async fn start_slave_server(limits: limits::Limits) -> (std::net::SocketAddr, CancellationToken, tokio::task::JoinHandle<()>) {
+        start_slave_server_tls(limits, None).await
+    }
+
+    async fn start_slave_server_tls(limits: limits::Limits, acceptor: Option<tokio_rustls::TlsAcceptor>) -> (std::net::SocketAddr, CancellationToken, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let token = CancellationToken::new();
+        let server = tokio::spawn(server::serve(listener, "SLAVE".to_string(), Arc::new(limits), acceptor, token.clone()));
+
+        (address, token, server)
+    }
+
+    // Reads from the stream until the response ends with the given text
+    async fn read_until(stream: &mut TcpStream, end: &str) -> String {
+        let mut response = vec![];
+        let mut buf = [0u8; 1024];
+
+        while !response.ends_with(end.as_bytes()) {
+            let read = stream.read(&mut buf).await.unwrap();
+            assert!(read > 0);
+            response.extend_from_slice(&buf[..read]);
+        }
+
+        String::from_utf8(response).unwrap()
+    }
+
+    #[tokio::test]
+    async fn shutdown_drains_in_flight_success() {
+        let (address, token, server) = start_slave_server(limits::Limits::default()).await;
+
+        // Both connections are served once, so we know they were accepted
+        let mut busy = TcpStream::connect(address).await.unwrap();
+        busy.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+        read_until(&mut busy, "cba").await;
+        let mut idle = TcpStream::connect(address).await.unwrap();
+        idle.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+        read_until(&mut idle, "cba").await;
+
+        // A request that started arriving before the shutdown is still
+        // answered, telling the client to close
+        busy.write_all(b"GET /reverse?text=def HTTP/1.1\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        token.cancel();
+        busy.write_all(b"\r\n").await.unwrap();
+
+        let mut response = String::new();
+        busy.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+        assert!(response.ends_with("fed"));
+
+        // While the idle connection is closed without an answer
+        let mut response = String::new();
+        idle.read_to_string(&mut response).await.unwrap();
+        assert!(response.is_empty());
+
+        tokio::time::timeout(Duration::from_secs(5), server).await.unwrap().unwrap();
+        assert!(TcpStream::connect(address).await.is_err());
+    }
+
+    // The slave's routes block their thread, so the test needs another one
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn shutdown_grace_period_error() {
+        let limits = limits::Limits { shutdown_grace: Duration::from_millis(200), ..Default::default() };
+        let (address, token, server) = start_slave_server(limits).await;
+
+        let mut busy = TcpStream::connect(address).await.unwrap();
+        busy.write_all(b"GET /sleep?seconds=2 HTTP/1.1\r\n\r\n").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // The server stops once the grace period is over, without waiting for
+        // the request to finish
+        token.cancel();
+        tokio::time::timeout(Duration::from_secs(2), server).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn admission_connections_error() {
+        let limits = limits::Limits { max_connections: 1, ..Default::default() };
+        let (address, _token, _server) = start_slave_server(limits).await;
+
+        let mut admitted = TcpStream::connect(address).await.unwrap();
+        admitted.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+        read_until(&mut admitted, "cba").await;
+
+        // Connections over the cap get their request answered and closed
+        let mut rejected = TcpStream::connect(address).await.unwrap();
+        rejected.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+        let mut response = String::new();
+        rejected.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains("Retry-After: 5\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+
+        // The slot is freed once the admitted connection closes
+        drop(admitted);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let mut next = TcpStream::connect(address).await.unwrap();
+        next.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+        assert!(read_until(&mut next, "cba").await.starts_with("HTTP/1.1 200 OK\r\n"));
+    }
+
+    #[tokio::test]
+    async fn admission_unread_error() {
+        let limits = limits::Limits { max_connections: 0, ..Default::default() };
+        let (address, _token, _server) = start_slave_server(limits).await;
+
+        // The 503 is sent without waiting for a request
+        let mut rejected = TcpStream::connect(address).await.unwrap();
+        let mut response = String::new();
+        tokio::time::timeout(Duration::from_secs(1), rejected.read_to_string(&mut response)).await.unwrap().unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[tokio::test]
+    async fn admission_requests_error() {
+        let limits = limits::Limits { max_in_flight: 0, retry_after: Duration::from_secs(1), ..Default::default() };
+        let (address, _token, _server) = start_slave_server(limits).await;
+
+        // The connection was admitted, so it's kept for the next request
+        let mut stream = TcpStream::connect(address).await.unwrap();
+
+        for _ in 0..2 {
+            stream.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+            let response = read_until(&mut stream, "Try again later.").await;
+            assert!(response.starts_with("HTTP/1.1 503 Service Unavailable\r\n"));
+            assert!(response.contains("Retry-After: 1\r\n"));
+            assert!(!response.contains("Connection: close\r\n"));
+        }
+    }
+
+    #[test]
+    fn admission_permits_success() {
+        let limits = limits::Limits { max_connections: 1, max_in_flight: 2, ..Default::default() };
+        let admission = admission::Admission::new(&limits);
+
+        let connection = admission.connection();
+        assert!(connection.is_some());
+        assert!(admission.connection().is_none());
+
+        let requests = [admission.request(), admission.request()];
+        assert!(requests.iter().all(|permit| permit.is_some()));
+        assert!(admission.request().is_none());
+
+        // Slots are freed once the permits are dropped
+        drop(connection);
+        drop(requests);
+        assert!(admission.connection().is_some());
+        assert!(admission.request().is_some());
+    }
+
+    // A self-signed certificate for localhost and 127.0.0.1 with its key. The
+    // certificate is also its own CA. Both are written to a directory of their
+    // own, removed once dropped
+    struct SelfSigned {
+        dir: std::path::PathBuf,
+        cert: String,
+        key: String,
+    }
+
+    impl Drop for SelfSigned {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn write_self_signed() -> SelfSigned {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string(), "127.0.0.1".to_string()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("os_p2-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).unwrap();
+
+        let cert = dir.join("cert.pem").to_string_lossy().to_string();
+        let key = dir.join("key.pem").to_string_lossy().to_string();
+        std::fs::write(&cert, certified.cert.pem()).unwrap();
+        std::fs::write(&key, certified.key_pair.serialize_pem()).unwrap();
+
+        SelfSigned { dir, cert, key }
+    }
+
+    // Sends the request on a pool of its own, so tests don't share connections
+    async fn send_request(address: std::net::SocketAddr, req: crate::models::request::HttpRequest, tls: Option<&ClientTls>) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+        pool::Pool::new(pool::PoolConfig::default()).send_tls(address, &CancellationToken::new(), req, tls).await
+    }
+
+    fn reverse_request(text: &str) -> crate::models::request::HttpRequest {
+        let mut req = crate::models::request::HttpRequest {
+            method: "GET".to_string(),
+            uri: vec!["reverse".to_string()],
+            version: "HTTP/1.1".to_string(),
+            ..Default::default()
+        };
+        req.params.insert("text".to_string(), text.to_string());
+        req
+    }
+
+    #[tokio::test]
+    async fn tls_async_request_success() {
+        let certs = write_self_signed();
+        let acceptor = tls::acceptor(&certs.cert, &certs.key).unwrap();
+        let (address, _token, _server) = start_slave_server_tls(limits::Limits::default(), Some(acceptor)).await;
+
+        // Without a server name, the IP address is checked
+        let client_tls = ClientTls::new(&certs.cert, None).unwrap();
+        let res = send_request(address, reverse_request("abc"), Some(&client_tls)).await.unwrap();
+
+        assert_eq!(res.status, 200);
+        assert_eq!(res.contents_str(), "cba");
+    }
+
+    // The sync client blocks its thread, so the server needs another one
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn tls_sync_request_success() {
+        let certs = write_self_signed();
+        let acceptor = tls::acceptor(&certs.cert, &certs.key).unwrap();
+        let (address, _token, _server) = start_slave_server_tls(limits::Limits::default(), Some(acceptor)).await;
+
+        let client_tls = ClientTls::new(&certs.cert, Some("localhost".to_string())).unwrap();
+        let res = tokio::task::spawn_blocking(move || {
+            client::send_sync_request_tls(address, reverse_request("abc"), Some(&client_tls)).unwrap()
+        }).await.unwrap();
+
+        assert_eq!(res.status, 200);
+        assert_eq!(res.contents_str(), "cba");
+    }
+
+    #[tokio::test]
+    async fn tls_untrusted_certificate_error() {
+        let certs = write_self_signed();
+        let other = write_self_signed();
+        let acceptor = tls::acceptor(&certs.cert, &certs.key).unwrap();
+        let (address, _token, _server) = start_slave_server_tls(limits::Limits::default(), Some(acceptor)).await;
+
+        let client_tls = ClientTls::new(&other.cert, None).unwrap();
+        let res = send_request(address, reverse_request("abc"), Some(&client_tls)).await;
+        assert!(res.is_err());
+
+        // The certificate doesn't include this name either
+        let client_tls = ClientTls::new(&certs.cert, Some("example.com".to_string())).unwrap();
+        let res = send_request(address, reverse_request("abc"), Some(&client_tls)).await;
+        assert!(res.is_err());
+    }
+
+    #[tokio::test]
+    async fn tls_plain_request_error() {
+        let certs = write_self_signed();
+        let acceptor = tls::acceptor(&certs.cert, &certs.key).unwrap();
+        let (address, _token, _server) = start_slave_server_tls(limits::Limits::default(), Some(acceptor)).await;
+
+        // Plain HTTP is never answered by a TLS listener
+        let res = send_request(address, reverse_request("abc"), None).await;
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn tls_missing_files_error() {
+        assert!(tls::acceptor("missing-cert.pem", "missing-key.pem").is_err());
+        assert!(ClientTls::new("missing-cert.pem", None).is_err());
+    }
+
+    // Feeds the whole input to a fresh parser, returning every request found
+    fn parse_all(pieces: &[&[u8]]) -> Result<Vec<crate::models::request::HttpRequest>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut parser = parser::RequestParser::new(limits::Limits::default());
+        let mut requests = vec![];
+
+        for piece in pieces {
+            parser.push(piece);
+
+            while let Some(req) = parser.advance()? {
+                requests.push(req);
+            }
+        }
+
+        Ok(requests)
+    }
+
+    #[test]
+    fn malformed_request_line_error() {
+        assert!(parse_all(&[b"GET\r\n"]).is_err());
+        assert!(parse_all(&[b"GET /\r\n"]).is_err());
+        assert!(parse_all(&[b"GET  / HTTP/1.1\r\n"]).is_err());
+        assert!(parse_all(&[b"GET / HTTP/1.1 extra\r\n"]).is_err());
+        assert!(parse_all(&[b"GET reverse HTTP/1.1\r\n"]).is_err());
+        assert!(parse_all(&[b"GET / FTP/1.1\r\n"]).is_err());
+        assert!(parse_all(&[b"G(T / HTTP/1.1\r\n"]).is_err());
+    }
+
+    #[test]
+    fn malformed_headers_error() {
+        assert!(parse_all(&[b"GET / HTTP/1.1\r\nNoColon\r\n\r\n"]).is_err());
+        assert!(parse_all(&[b"GET / HTTP/1.1\r\n: value\r\n\r\n"]).is_err());
+        assert!(parse_all(&[b"GET / HTTP/1.1\r\nName : value\r\n\r\n"]).is_err());
+        assert!(parse_all(&[b"POST / HTTP/1.1\r\nContent-Length: 2\r\nContent-Length: 3\r\n\r\n{}"]).is_err());
+        assert!(parse_all(&[b"POST / HTTP/1.1\r\nContent-Length: +2\r\n\r\n{}"]).is_err());
+    }
+
+    #[test]
+    fn pipelined_leftover_success() {
+        let requests = parse_all(&[b"GET /reverse?text=a HTTP/1.1\r\n\r\nGET /rev", b"erse?text=b HTTP/1.1\r\n\r\nGET"]).unwrap();
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].param("text").unwrap(), "a");
+        assert_eq!(requests[1].param("text").unwrap(), "b");
+    }
+
+    // Valid requests, with and without a body, used by the properties below
+    fn valid_request() -> impl Strategy<Value = Vec<u8>> {
+        ("[a-z]{1,8}", "[a-zA-Z0-9 ]{0,16}", proptest::bool::ANY, proptest::bool::ANY).prop_map(|(path, text, chunked, json)| {
+            let query = parser::percent_encode(&text);
+
+            if !json {
+                return format!("GET /{}?text={} HTTP/1.1\r\nHost: a:7878\r\n\r\n", path, query).into_bytes();
+            }
+
+            let body = format!("{{\"text\": \"{}\"}}", text);
+
+            if chunked {
+                let chunks: String = body.as_bytes().chunks(3)
+                    .map(|chunk| String::from_utf8(response::encode_chunk(chunk)).unwrap())
+                    .collect();
+                format!("POST /{} HTTP/1.1\r\nContent-Type: application/json\r\nTransfer-Encoding: chunked\r\n\r\n{}0\r\n\r\n", path, chunks).into_bytes()
+            } else {
+                format!("POST /{} HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}", path, body.len(), body).into_bytes()
+            }
+        })
+    }
+
+    // Splits the input at the given points, ignoring the ones out of range
+    fn split_at_points<'a>(input: &'a [u8], points: &[usize]) -> Vec<&'a [u8]> {
+        let mut points: Vec<usize> = points.iter().map(|point| point % (input.len() + 1)).collect();
+        points.sort();
+
+        let mut pieces = vec![];
+        let mut start = 0;
+
+        for point in points {
+            pieces.push(&input[start..point]);
+            start = point;
+        }
+
+        pieces.push(&input[start..]);
+        pieces
+    }
+
+    proptest! {
+        #[test]
+        fn parser_never_panics(input in proptest::collection::vec(any::<u8>(), 0..512), points in proptest::collection::vec(any::<usize>(), 0..8)) {
+            let _ = parse_all(&split_at_points(&input, &points));
+        }
+
+        #[test]
+        fn parser_never_panics_on_near_valid(mut input in valid_request(), index in any::<usize>(), byte in any::<u8>(), points in proptest::collection::vec(any::<usize>(), 0..8)) {
+            // A single corrupted byte shouldn't be able to make it panic
+            let len = input.len();
+            input[index % len] = byte;
+            let _ = parse_all(&split_at_points(&input, &points));
+        }
+
+        #[test]
+        fn parser_split_independent(input in valid_request(), points in proptest::collection::vec(any::<usize>(), 0..8)) {
+            let whole = parse_all(&[&input]).unwrap();
+            let split = parse_all(&split_at_points(&input, &points)).unwrap();
+            let bytes: Vec<&[u8]> = input.chunks(1).collect();
+            let byte_by_byte = parse_all(&bytes).unwrap();
+
+            prop_assert_eq!(whole.len(), 1);
+            prop_assert_eq!(split.len(), 1);
+            prop_assert_eq!(byte_by_byte.len(), 1);
+            prop_assert_eq!(format!("{}", &whole[0]), format!("{}", &split[0]));
+            prop_assert_eq!(format!("{}", &whole[0]), format!("{}", &byte_by_byte[0]));
+        }
+
+        #[test]
+        fn parser_pipelined(first in valid_request(), second in valid_request(), points in proptest::collection::vec(any::<usize>(), 0..8)) {
+            let input = [first.clone(), second.clone()].concat();
+            let requests = parse_all(&split_at_points(&input, &points)).unwrap();
+
+            prop_assert_eq!(requests.len(), 2);
+            prop_assert_eq!(format!("{}", &requests[0]), format!("{}", &parse_all(&[&first]).unwrap()[0]));
+            prop_assert_eq!(format!("{}", &requests[1]), format!("{}", &parse_all(&[&second]).unwrap()[0]));
+        }
+    }
+
+    fn test_router() -> router::Router<HttpResponse> {
+        router::Router::new()
+            .route(router::Route::sync("GET", "/double", |_, ctx| {
+                let num = *ctx.get::<u64>("num");
+                HttpResponse::new("HTTP/1.1".to_string(), 200, crate::models::headers::Headers::new(), (num * 2).to_string())
+            }).param::<u64>("num"))
+            .route(router::Route::sync("POST", "/double", |_, _| HttpResponse::basic(201)))
+            .route(router::Route::asynchronous("GET", "/later", |_, _| Box::pin(async { HttpResponse::basic(200) })))
+            .route(router::Route::sync("GET", "/internal", |_, _| HttpResponse::basic(200)).hidden())
+            .route(router::Route::sync("GET", "/jobs/{id}", |_, ctx| HttpResponse::new("HTTP/1.1".to_string(), 200, crate::models::headers::Headers::new(), ctx.capture("id").to_string())))
+            .route(router::Route::sync("GET", "/jobs/latest", |_, _| HttpResponse::basic(204)))
+            .route(router::Route::sync("GET", "/jobs/{id}/files/{name}", |_, ctx| {
+                let contents = format!("{}:{}", ctx.capture("id"), ctx.capture("name"));
+                HttpResponse::new("HTTP/1.1".to_string(), 200, crate::models::headers::Headers::new(), contents)
+            }))
+    }
+
+    fn test_request(method: &str, uri: &str, params: &[(&str, &str)]) -> crate::models::request::HttpRequest {
+        let mut req = crate::models::request::HttpRequest {
+            method: method.to_string(),
+            uri: uri.split("/").map(|segment| segment.to_string()).collect(),
+            version: "HTTP/1.1".to_string(),
+            ..Default::default()
+        };
+
+        for (key, value) in params {
+            req.params.insert(key.to_string(), value.to_string());
+        }
+
+        req
+    }
+
+    fn test_remote() -> std::net::SocketAddr {
+        "127.0.0.1:1234".parse().unwrap()
+    }
+
+    #[test]
+    fn router_params_success() {
+        let res = test_router().handle_sync(test_request("GET", "double", &[("num", "21")]), test_remote()).unwrap();
+        assert_eq!(res.status, 200);
+        assert_eq!(res.contents_str(), "42");
+
+        let res = test_router().handle_sync(test_request("POST", "double", &[]), test_remote()).unwrap();
+        assert_eq!(res.status, 201);
+    }
+
+    #[test]
+    fn router_params_error() {
+        let Err(res) = test_router().handle_sync(test_request("GET", "double", &[]), test_remote()) else {
+            panic!("Missing parameter accepted");
+        };
+        assert_eq!(res.status, 400);
+        assert_eq!(res.contents_str(), "Missing parameter: num");
+
+        let Err(res) = test_router().handle_sync(test_request("GET", "double", &[("num", "-1")]), test_remote()) else {
+            panic!("Invalid parameter accepted");
+        };
+        assert_eq!(res.status, 400);
+        assert_eq!(res.contents_str(), "Unable to parse num param!");
+    }
+
+    #[test]
+    fn router_methods_error() {
+        let Err(res) = test_router().handle_sync(test_request("DELETE", "double", &[]), test_remote()) else {
+            panic!("Method not allowed accepted");
+        };
+        assert_eq!(res.status, 405);
+        assert_eq!(res.headers.get("Allow").unwrap(), "GET, POST, HEAD, OPTIONS");
+
+        let Err(res) = test_router().handle_sync(test_request("GET", "missing", &[]), test_remote()) else {
+            panic!("Unknown route accepted");
+        };
+        assert_eq!(res.status, 404);
+    }
+
+    #[tokio::test]
+    async fn router_async_success() {
+        let res = test_router().handle(test_request("GET", "later", &[]), test_remote()).await.unwrap();
+        assert_eq!(res.status, 200);
+
+        let res = test_router().handle(test_request("GET", "double", &[("num", "1")]), test_remote()).await.unwrap();
+        assert_eq!(res.contents_str(), "2");
+    }
+
+    #[test]
+    fn router_async_from_sync_error() {
+        let Err(res) = test_router().handle_sync(test_request("GET", "later", &[]), test_remote()) else {
+            panic!("Async route handled synchronously");
+        };
+        assert_eq!(res.status, 500);
+    }
+
+    #[test]
+    fn router_help_success() {
+        let help = test_router().help();
+
+        assert!(help.contains("GET /double?num=<u64>\n"));
+        assert!(help.contains("POST /double\n"));
+        assert!(help.contains("GET /later\n"));
+        assert!(!help.contains("/internal"));
+    }
+
+    #[test]
+    fn router_captures_success() {
+        let res = test_router().handle_sync(test_request("GET", "jobs/7", &[]), test_remote()).unwrap();
+        assert_eq!(res.contents_str(), "7");
+
+        let res = test_router().handle_sync(test_request("GET", "jobs/7/files/a.txt", &[]), test_remote()).unwrap();
+        assert_eq!(res.contents_str(), "7:a.txt");
+
+        // Literal segments take precedence over captures
+        let res = test_router().handle_sync(test_request("GET", "jobs/latest", &[]), test_remote()).unwrap();
+        assert_eq!(res.status, 204);
+    }
+
+    #[test]
+    fn router_sub_path_error() {
+        for uri in ["double/extra", "jobs", "jobs/7/files", "jobs/7/stats", "internal/x/y"] {
+            let Err(res) = test_router().handle_sync(test_request("GET", uri, &[("num", "1")]), test_remote()) else {
+                panic!("Unknown sub-path {} accepted", uri);
+            };
+            assert_eq!(res.status, 404);
+        }
+    }
+
+    #[test]
+    fn router_help_templates_success() {
+        assert!(test_router().help().contains("GET /jobs/{id}/files/{name}\n"));
+    }
+
+    #[test]
+    fn multipart_success() {
+        let body = b"preamble\r\n--XyZ\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\nnotes.txt\r\n--XyZ\r\nContent-Disposition: form-data; name=\"file\"; filename=\"a;b.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n\x00\xff\r\n--Xy\r\n\r\n--XyZ--\r\nepilogue";
+        let parts = multipart::parse("multipart/form-data; boundary=\"XyZ\"", body).unwrap();
+
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0].name, "name");
+        assert_eq!(parts[0].filename, None);
+        assert_eq!(parts[0].content, b"notes.txt");
+        assert_eq!(parts[1].name, "file");
+        assert_eq!(parts[1].filename.as_deref(), Some("a;b.bin"));
+        assert_eq!(parts[1].content, b"\x00\xff\r\n--Xy\r\n");
+    }
+
+    #[test]
+    fn multipart_error() {
+        let content_type = "multipart/form-data; boundary=b";
+
+        // Missing boundary, closing delimiter and disposition
+        assert!(multipart::parse("multipart/form-data", b"--b--").is_err());
+        assert!(multipart::parse(content_type, b"--b\r\nContent-Disposition: form-data; name=\"a\"\r\n\r\nhola").is_err());
+        assert!(multipart::parse(content_type, b"--b\r\nContent-Type: text/plain\r\n\r\nhola\r\n--b--").is_err());
+        assert!(multipart::is_multipart("Multipart/Form-Data; boundary=b"));
+        assert!(!multipart::is_multipart("text/plain"));
+    }
+
+    fn file_request(headers: &[(&str, &str)]) -> crate::models::request::HttpRequest {
+        let mut req = test_request("GET", "files/notes.txt", &[]);
+
+        for (name, value) in headers {
+            req.headers.insert(name.to_string(), value.to_string());
+        }
+
+        req
+    }
+
+    #[test]
+    fn static_files_success() {
+        let content = b"0123456789".to_vec();
+        let etag = static_files::etag(&content);
+        let modified = std::time::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+        let res = static_files::serve(&file_request(&[]), content.clone(), Some(modified), "text/plain");
+        assert_eq!(res.status, 200);
+        assert_eq!(res.contents, content);
+        assert_eq!(res.headers.get("ETag"), Some(&etag));
+        assert_eq!(res.headers.get("Last-Modified").unwrap(), "Tue, 14 Nov 2023 22:13:20 GMT");
+        assert_eq!(res.headers.get("Accept-Ranges").unwrap(), "bytes");
+
+        // Both validators, including weak tags from compressed responses
+        let weak = format!("\"other\", W/{}", etag);
+        let res = static_files::serve(&file_request(&[("If-None-Match", &weak)]), content.clone(), Some(modified), "text/plain");
+        assert_eq!(res.status, 304);
+        assert!(res.contents.is_empty());
+        assert!(!res.to_bytes().windows(14).any(|window| window == b"Content-Length"));
+
+        let res = static_files::serve(&file_request(&[("If-Modified-Since", "Tue, 14 Nov 2023 22:13:20 GMT")]), content.clone(), Some(modified), "text/plain");
+        assert_eq!(res.status, 304);
+
+        let res = static_files::serve(&file_request(&[("If-Modified-Since", "Tue, 14 Nov 2023 22:13:19 GMT")]), content.clone(), Some(modified), "text/plain");
+        assert_eq!(res.status, 200);
+
+        // Ranges with both ends, an open end and a suffix
+        for (range, expected, header) in [("bytes=2-4", "234", "bytes 2-4/10"), ("bytes=7-", "789", "bytes 7-9/10"), ("bytes=-2", "89", "bytes 8-9/10"), ("bytes=8-100", "89", "bytes 8-9/10")] {
+            let res = static_files::serve(&file_request(&[("Range", range)]), content.clone(), None, "text/plain");
+            assert_eq!(res.status, 206);
+            assert_eq!(res.contents_str(), expected);
+            assert_eq!(res.headers.get("Content-Range").unwrap(), header);
+        }
+
+        let res = static_files::serve(&file_request(&[("Range", "bytes=0-0"), ("If-Range", &etag)]), content.clone(), None, "text/plain");
+        assert_eq!(res.status, 206);
+        assert_eq!(res.contents_str(), "0");
+    }
+
+    #[test]
+    fn static_files_error() {
+        let content = b"0123456789".to_vec();
+
+        for range in ["bytes=10-", "bytes=20-30", "bytes=-0"] {
+            let res = static_files::serve(&file_request(&[("Range", range)]), content.clone(), None, "text/plain");
+            assert_eq!(res.status, 416);
+            assert_eq!(res.headers.get("Content-Range").unwrap(), "bytes */10");
+        }
+
+        // Multiple, malformed and outdated ranges send the whole file
+        for headers in [vec![("Range", "bytes=0-1,4-5")], vec![("Range", "bytes=5-2")], vec![("Range", "items=0-1")], vec![("Range", "bytes=0-1"), ("If-Range", "\"old\"")]] {
+            let res = static_files::serve(&file_request(&headers), content.clone(), None, "text/plain");
+            assert_eq!(res.status, 200);
+            assert_eq!(res.contents, content);
+        }
+
+        // Without a modification time dates can't be compared
+        let res = static_files::serve(&file_request(&[("If-Modified-Since", "Tue, 14 Nov 2023 22:13:20 GMT")]), content.clone(), None, "text/plain");
+        assert_eq!(res.status, 200);
+        let res = static_files::serve(&file_request(&[("If-None-Match", "\"other\"")]), content, None, "text/plain");
+        assert_eq!(res.status, 200);
+
+        assert_eq!(static_files::content_type("data.JSON"), "application/json");
+        assert_eq!(static_files::content_type("archive"), "application/octet-stream");
+    }
+
+    fn test_cors() -> cors::Cors {
+        cors::Cors {
+            origins: vec!["https://dashboard.example".to_string()],
+            ..Default::default()
+        }
+    }
+
+    fn preflight_request(origin: &str) -> crate::models::request::HttpRequest {
+        let mut req = test_request("OPTIONS", "files", &[]);
+        req.headers.insert("Origin".to_string(), origin.to_string());
+        req.headers.insert("Access-Control-Request-Method".to_string(), "POST".to_string());
+        req
+    }
+
+    #[test]
+    fn cors_preflight_success() {
+        let res = test_cors().preflight(&preflight_request("https://dashboard.example")).unwrap();
+
+        assert_eq!(res.status, 204);
+        assert_eq!(res.headers.get("Access-Control-Allow-Origin").unwrap(), "https://dashboard.example");
+        assert_eq!(res.headers.get("Access-Control-Allow-Methods").unwrap(), "GET, HEAD, POST, DELETE, OPTIONS");
+        assert_eq!(res.headers.get("Access-Control-Allow-Headers").unwrap(), "Content-Type");
+        assert_eq!(res.headers.get("Access-Control-Max-Age").unwrap(), "600");
+        assert!(res.headers.has_token("Vary", "Origin"));
+        assert!(!res.headers.contains("Access-Control-Allow-Credentials"));
+
+        // Credentials need the origin and headers echoed instead of a *
+        let cors = cors::Cors { origins: vec!["*".to_string()], headers: vec!["*".to_string()], credentials: true, ..Default::default() };
+        let mut req = preflight_request("https://other.example");
+        req.headers.insert("Access-Control-Request-Headers".to_string(), "X-Token".to_string());
+        let res = cors.preflight(&req).unwrap();
+
+        assert_eq!(res.headers.get("Access-Control-Allow-Origin").unwrap(), "https://other.example");
+        assert_eq!(res.headers.get("Access-Control-Allow-Headers").unwrap(), "X-Token");
+        assert_eq!(res.headers.get("Access-Control-Allow-Credentials").unwrap(), "true");
+    }
+
+    #[test]
+    fn cors_preflight_error() {
+        // Other origins and plain OPTIONS requests reach the routes
+        assert!(test_cors().preflight(&preflight_request("https://evil.example")).is_none());
+
+        let mut req = preflight_request("https://dashboard.example");
+        req.headers.remove("Access-Control-Request-Method");
+        assert!(test_cors().preflight(&req).is_none());
+    }
+
+    #[test]
+    fn cors_decorate_success() {
+        let mut res = Response::HTTP(HttpResponse::text(200, "hola"));
+        test_cors().decorate(Some("https://dashboard.example"), &mut res);
+
+        let Response::HTTP(res) = res else { unreachable!() };
+        assert_eq!(res.headers.get("Access-Control-Allow-Origin").unwrap(), "https://dashboard.example");
+        assert!(res.headers.has_token("Vary", "Origin"));
+
+        // Other origins don't get the headers, though caches still need Vary
+        let mut res = Response::HTTP(HttpResponse::text(200, "hola"));
+        test_cors().decorate(Some("https://evil.example"), &mut res);
+
+        let Response::HTTP(mut res) = res else { unreachable!() };
+        assert!(!res.headers.contains("Access-Control-Allow-Origin"));
+        res.headers.add_token("Vary", "Accept-Encoding");
+        res.headers.add_token("Vary", "origin");
+        assert_eq!(res.headers.get("Vary").unwrap(), "Origin, Accept-Encoding");
+    }
+
+    #[tokio::test]
+    async fn request_id_success() {
+        let mut req = test_request("GET", "reverse", &[]);
+        req.headers.insert(request_id::HEADER.to_string(), "abc".to_string());
+        assert_eq!(request_id::from_request(&req), "abc");
+
+        // Missing or unprintable IDs are replaced
+        for id in ["", "a b", &"a".repeat(129)] {
+            req.headers.insert(request_id::HEADER.to_string(), id.to_string());
+            assert_eq!(request_id::from_request(&req).len(), 36);
+        }
+
+        assert_eq!(request_id::current(), None);
+
+        request_id::scope("abc".to_string(), async {
+            // Spawned tasks only know the request if it's propagated
+            let spawned = tokio::spawn(request_id::propagate(async { request_id::current() }));
+            assert_eq!(spawned.await.unwrap().as_deref(), Some("abc"));
+            assert_eq!(tokio::spawn(async { request_id::current() }).await.unwrap(), None);
+
+            let mut forwarded = test_request("GET", "reverse", &[]);
+            forwarded.headers.insert(request_id::HEADER.to_string(), "abc".to_string());
+            request_id::tag_forwarded(&mut forwarded);
+            assert_eq!(forwarded.headers.get(request_id::HEADER).unwrap(), "abc");
+
+            // Each request created for the client's gets its own sub-ID
+            let mut aggregate = test_request("GET", "counttotal", &[]);
+            request_id::tag_forwarded(&mut aggregate);
+            assert_eq!(aggregate.headers.get(request_id::HEADER).unwrap(), "abc.1");
+
+            request_id::tag_partial(&mut forwarded);
+            assert_eq!(forwarded.headers.get(request_id::HEADER).unwrap(), "abc.2");
+        }).await;
+    }
+
+    #[tokio::test]
+    async fn request_id_echo_success() {
+        let (address, _token, _server) = start_slave_server(limits::Limits::default()).await;
+        let mut stream = TcpStream::connect(address).await.unwrap();
+
+        stream.write_all(b"GET /reverse?text=abc HTTP/1.1\r\nX-Request-Id: job-7.3\r\n\r\n").await.unwrap();
+        assert!(read_until(&mut stream, "cba").await.contains("X-Request-Id: job-7.3\r\n"));
+
+        stream.write_all(b"GET /reverse?text=abc HTTP/1.1\r\n\r\n").await.unwrap();
+        assert!(read_until(&mut stream, "cba").await.contains("X-Request-Id: "));
+    }
+
+    #[tokio::test]
+    async fn client_large_response_success() {
+        let (address, _token, _server) = start_slave_server(limits::Limits::default()).await;
+
+        let mut req = test_request("GET", "random", &[("count", "5000"), ("min", "100000"), ("max", "999999")]);
+        req.uri = vec!["random".to_string()];
+        let res = send_request(address, req, None).await.unwrap();
+
+        assert_eq!(res.status, 200);
+        assert!(res.contents.len() > 4096);
+        assert_eq!(res.headers.get("Content-Length").unwrap(), &res.contents.len().to_string());
+    }
+
+    #[tokio::test]
+    async fn pool_slave_reuse_success() {
+        let (address, _token, _server) = start_slave_server(limits::Limits::default()).await;
+        let pool = pool::Pool::new(pool::PoolConfig::default());
+        let token = CancellationToken::new();
+
+        // The slaves keep connections open between requests
+        for text in ["abc", "defg"] {
+            let res = pool.send(address, &token, reverse_request(text)).await.unwrap();
+            assert_eq!(res.contents_str(), text.chars().rev().collect::<String>());
+        }
+    }
+}
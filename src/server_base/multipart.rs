@@ -0,0 +1,120 @@
+use crate::errors::parse::ParseUriError;
+use crate::models::headers::Headers;
+
+use super::parser::find;
+
+type ParseResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// A field of a multipart/form-data body
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub name: String,
+    // Only set for file fields
+    pub filename: Option<String>,
+    pub content: Vec<u8>,
+}
+
+// Whether a Content-Type is the one of multipart/form-data bodies
+pub fn is_multipart(content_type: &str) -> bool {
+    media_type(content_type).eq_ignore_ascii_case("multipart/form-data")
+}
+
+// Splits a body into its parts, using the boundary of the Content-Type. Parts
+// are kept as bytes, as files don't need to be text
+pub fn parse(content_type: &str, body: &[u8]) -> ParseResult<Vec<Part>> {
+    let Some(boundary) = parameter(content_type, "boundary").filter(|boundary| !boundary.is_empty()) else {
+        return Err(Box::new(ParseUriError));
+    };
+
+    let delimiter = format!("\r\n--{}", boundary).into_bytes();
+
+    // Anything before the first delimiter is a preamble we can ignore. The
+    // first one doesn't need the CRLF, as it can start the body
+    let mut rest = if body.starts_with(&delimiter[2..]) {
+        &body[delimiter.len() - 2..]
+    } else {
+        let start = find(body, &delimiter).ok_or(ParseUriError)?;
+        &body[start + delimiter.len()..]
+    };
+
+    let mut parts = vec![];
+
+    loop {
+        // The last delimiter is followed by two dashes
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+
+        let Some(after) = rest.strip_prefix(b"\r\n") else {
+            return Err(Box::new(ParseUriError));
+        };
+
+        // Parts without headers start right away with the empty line
+        let (head, content) = match after.strip_prefix(b"\r\n") {
+            Some(content) => (&after[..0], content),
+            None => {
+                let end = find(after, b"\r\n\r\n").ok_or(ParseUriError)?;
+                (&after[..end], &after[end + 4..])
+            },
+        };
+
+        let end = find(content, &delimiter).ok_or(ParseUriError)?;
+        parts.push(parse_part(head, content[..end].to_vec())?);
+        rest = &content[end + delimiter.len()..];
+    }
+}
+
+fn parse_part(head: &[u8], content: Vec<u8>) -> ParseResult<Part> {
+    let head = std::str::from_utf8(head).map_err(|_| ParseUriError)?;
+    let mut headers = Headers::new();
+
+    for line in head.split("\r\n").filter(|line| !line.is_empty()) {
+        let (name, value) = line.split_once(":").ok_or(ParseUriError)?;
+        headers.append(name.trim().to_string(), value.trim().to_string());
+    }
+
+    // Every part needs to say which field it is
+    let disposition = headers.get("Content-Disposition").ok_or(ParseUriError)?;
+
+    if !media_type(disposition).eq_ignore_ascii_case("form-data") {
+        return Err(Box::new(ParseUriError));
+    }
+
+    let name = parameter(disposition, "name").ok_or(ParseUriError)?;
+    let filename = parameter(disposition, "filename");
+
+    Ok(Part { name, filename, content })
+}
+
+// The value before any parameter, like the type of a Content-Type
+fn media_type(value: &str) -> &str {
+    value.split(";").next().unwrap_or("").trim()
+}
+
+// Looks up a parameter of a header value, like the boundary of a
+// Content-Type. Values can be quoted, so they can have semicolons
+fn parameter(value: &str, key: &str) -> Option<String> {
+    let mut params = value.split_once(";")?.1;
+
+    loop {
+        let (name, rest) = params.split_once("=")?;
+        let rest = rest.trim_start();
+
+        let (found, rest) = match rest.strip_prefix("\"") {
+            Some(quoted) => {
+                let end = quoted.find("\"")?;
+                (quoted[..end].to_string(), &quoted[end + 1..])
+            },
+            None => {
+                let end = rest.find(";").unwrap_or(rest.len());
+                (rest[..end].trim().to_string(), &rest[end..])
+            },
+        };
+
+        if name.trim().eq_ignore_ascii_case(key) {
+            return Some(found);
+        }
+
+        params = rest.split_once(";")?.1;
+    }
+}
@@ -1,119 +1,467 @@
-use tokio::{io::AsyncBufReadExt, net::TcpStream};
-use tokio::io::{AsyncReadExt, BufReader};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::time::{timeout_at, Instant};
 
 use serde_json::Value;
 use std::collections::HashMap;
 
+use crate::models::headers::Headers;
 use crate::models::request::{Body, HttpRequest};
 use crate::errors::*;
 
-// This function parses a valid http request message and returns a struct or
-// throws different errors to the caller
-pub async fn parse(mut message: TcpStream) -> Result<HttpRequest, Box<dyn std::error::Error + Send + Sync>> {
-    let mut buf_reader = BufReader::new(&mut message);
-
-    let (method, uri, params, version) = parse_request_line(&mut buf_reader).await?;
-    let headers = parse_headers(&mut buf_reader).await;
-    let body = parse_body(&mut buf_reader, &headers).await?;
+use super::limits::Limits;
+
+type ParseResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Where the parser is within the current request
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    RequestLine,
+    Headers,
+    // Bytes left of a body framed by its content length
+    Body(usize),
+    ChunkSize,
+    // Bytes left of the current chunk
+    ChunkData(usize),
+    // The CRLF that follows each chunk's data
+    ChunkEnd,
+    Trailers,
+    Done,
+}
 
-    Ok(HttpRequest::new(method, uri, params, version, headers, body))
+// Incremental HTTP request parser. Bytes are pushed as they arrive and the
+// parser advances as far as they allow, so it never blocks and never indexes
+// data it doesn't have. Bytes left after a complete request are kept for the
+// next one, which is how pipelined requests are handled
+#[derive(Debug)]
+pub struct RequestParser {
+    limits: Limits,
+    state: State,
+    buffer: Vec<u8>,
+    method: String,
+    uri: Vec<String>,
+    query: Vec<(String, String)>,
+    version: String,
+    headers: Headers,
+    headers_size: usize,
+    content: Vec<u8>,
 }
 
-// This function parses the 1st line of the http message,
-// returning the method, version, URI and query parameters used.
-async fn parse_request_line(buf_reader: &mut BufReader<&mut TcpStream>)
-    -> Result<(String, Vec<String>, HashMap<String, String>, String), parse::ParseUriError> {
-    
-    let mut request_line = String::new();
-    let _ = buf_reader.read_line(&mut request_line).await;
+impl RequestParser {
+    pub fn new(limits: Limits) -> RequestParser {
+        RequestParser {
+            limits,
+            state: State::RequestLine,
+            buffer: vec![],
+            method: String::new(),
+            uri: vec![],
+            query: vec![],
+            version: String::new(),
+            headers: Headers::new(),
+            headers_size: 0,
+            content: vec![],
+        }
+    }
 
-    // The first-line values are space-separated
-    let request_line: Vec<&str> = request_line.trim().split(" ").collect();
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
 
-    // The URI and query params are delimited by the ? char
-    let uri_line: Vec<&str> = request_line[1].split("?").collect();
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
 
-    // The resulting vector can have a length of 1..2
-    // Thus, throwing an error back to the caller
-    if !(uri_line.len() < 3 && uri_line.len() > 0) {
-        return Err(parse::ParseUriError);
+    // Whether there are received bytes that weren't parsed yet
+    pub fn has_buffered(&self) -> bool {
+        !self.buffer.is_empty()
     }
 
-    // The 1st value is the actual URI
-    let unchecked_uri: Vec<&str> = uri_line[0].split("/").collect();
-    
-    // Given the segments are separated by a slash, we add root to the vector
-    let mut uri: Vec<String> = vec![];
+    // The head is complete once we are reading the body, which has its own
+    // deadline
+    pub fn in_body(&self) -> bool {
+        !matches!(self.state, State::RequestLine | State::Headers)
+    }
+
+    // Parses as much of the buffered bytes as possible. Returns the request
+    // once it's complete, or None if more bytes are needed. After an error,
+    // the parser shouldn't be used again as the framing is lost
+    pub fn advance(&mut self) -> ParseResult<Option<HttpRequest>> {
+        loop {
+            match self.state {
+                State::RequestLine => {
+                    let Some(line) = self.take_line(self.limits.max_request_line, || Box::new(limits::UriTooLongError))? else {
+                        return Ok(None);
+                    };
+
+                    // Empty lines before a request are allowed and ignored
+                    if line.is_empty() {
+                        continue;
+                    }
+
+                    self.parse_request_line(&line)?;
+                    self.state = State::Headers;
+                },
+                State::Headers => {
+                    // A single line can't be larger than what's left of the
+                    // allowed size
+                    let remaining = self.limits.max_headers_size.saturating_sub(self.headers_size);
+
+                    let Some(line) = self.take_line(remaining, || Box::new(limits::HeadersTooLargeError))? else {
+                        return Ok(None);
+                    };
+
+                    // The headers and body are separated by an empty line
+                    if line.is_empty() {
+                        self.state = self.body_state()?;
+                        continue;
+                    }
+
+                    self.headers_size += line.len() + 2;
+                    self.parse_header(&line)?;
+                },
+                State::Body(remaining) => {
+                    let remaining = self.take_content(remaining);
+
+                    if remaining > 0 {
+                        self.state = State::Body(remaining);
+                        return Ok(None);
+                    }
+
+                    self.state = State::Done;
+                },
+                State::ChunkSize => {
+                    // The size line is small, anything longer than the request
+                    // line limit is not a valid one
+                    let Some(line) = self.take_line(self.limits.max_request_line, || Box::new(parse::ParseUriError))? else {
+                        return Ok(None);
+                    };
+
+                    let size = parse_chunk_size(&line)?;
+
+                    if size == 0 {
+                        self.state = State::Trailers;
+                        continue;
+                    }
+
+                    // The total is checked before reading the chunk
+                    if self.content.len().saturating_add(size) > self.limits.max_body_size {
+                        return Err(Box::new(limits::PayloadTooLargeError));
+                    }
+
+                    self.state = State::ChunkData(size);
+                },
+                State::ChunkData(remaining) => {
+                    let remaining = self.take_content(remaining);
+
+                    if remaining > 0 {
+                        self.state = State::ChunkData(remaining);
+                        return Ok(None);
+                    }
+
+                    self.state = State::ChunkEnd;
+                },
+                State::ChunkEnd => {
+                    let Some(line) = self.take_line(2, || Box::new(parse::ParseUriError))? else {
+                        return Ok(None);
+                    };
+
+                    if !line.is_empty() {
+                        return Err(Box::new(parse::ParseUriError));
+                    }
+
+                    self.state = State::ChunkSize;
+                },
+                State::Trailers => {
+                    // Optional trailer headers can follow the last chunk until
+                    // an empty line. We don't use them, but they count
+                    // towards the headers size
+                    let remaining = self.limits.max_headers_size.saturating_sub(self.headers_size);
+
+                    let Some(line) = self.take_line(remaining, || Box::new(limits::HeadersTooLargeError))? else {
+                        return Ok(None);
+                    };
+
+                    if line.is_empty() {
+                        self.state = State::Done;
+                    } else {
+                        self.headers_size += line.len() + 2;
+                    }
+                },
+                State::Done => {
+                    return self.finish().map(Some);
+                },
+            }
+        }
+    }
+
+    // Takes a line off the buffer, without its line ending. Lines are ended by
+    // CRLF, though a lone LF is also accepted. None means the line isn't
+    // complete yet. If it's already longer than max bytes, the given error is
+    // thrown instead
+    fn take_line(&mut self, max: usize, too_long: fn() -> Box<dyn std::error::Error + Send + Sync>) -> ParseResult<Option<String>> {
+        let Some(end) = self.buffer.iter().position(|byte| *byte == b'\n') else {
+            if self.buffer.len() > max {
+                return Err(too_long());
+            }
+
+            return Ok(None);
+        };
+
+        if end + 1 > max {
+            return Err(too_long());
+        }
 
-    let mut len_to_check = unchecked_uri.len()-1;
+        let mut line: Vec<u8> = self.buffer.drain(..=end).collect();
+        line.pop();
 
-    // If the last string in the vector is not empty (meaning a trailing slash),
-    // Then we increment the variable to include it in the final URI
-    if unchecked_uri[len_to_check] != "" {
-        len_to_check += 1;
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+
+        // Lines that aren't valid UTF-8 can't be part of a valid request
+        String::from_utf8(line).map(Some).map_err(|_| Box::new(parse::ParseUriError).into())
     }
 
-    // If there are empty strings in the vector, it means there were multiple
-    // consecutive slashes. Thus, it's an invalid URI ad we return early with
-    // an error. Else, we add the string to the vector
-    for i in 1..(len_to_check) {
-        if unchecked_uri[i] == "" {
-            return Err(parse::ParseUriError);
+    // Moves up to the remaining amount of body bytes from the buffer to the
+    // content, returning how many are still missing
+    fn take_content(&mut self, remaining: usize) -> usize {
+        let available = remaining.min(self.buffer.len());
+        self.content.extend(self.buffer.drain(..available));
+        remaining - available
+    }
+
+    // This function parses the 1st line of the http message, storing the
+    // method, version, URI and query parameters used.
+    fn parse_request_line(&mut self, line: &str) -> ParseResult<()> {
+        // The first-line values are separated by single spaces
+        let parts: Vec<&str> = line.split(" ").collect();
+
+        let [method, target, version] = parts.as_slice() else {
+            return Err(Box::new(parse::ParseUriError));
+        };
+
+        if method.is_empty() || !method.bytes().all(is_token_char) {
+            return Err(Box::new(parse::ParseUriError));
+        }
+
+        // Unknown versions are still parsed, so the server can answer them
+        // with a 505
+        if !version.starts_with("HTTP/") {
+            return Err(Box::new(parse::ParseUriError));
+        }
+
+        // The URI and query params are delimited by the first ? char, as the
+        // query itself is allowed to contain more of them
+        let (path, query) = match target.split_once("?") {
+            Some((path, query)) => (path, Some(query)),
+            None => (*target, None),
+        };
+
+        self.method = method.to_string();
+        // The asterisk form only targets the whole server, for OPTIONS
+        self.uri = if path == "*" && *method == "OPTIONS" {
+            vec!["*".to_string()]
+        } else {
+            parse_path(path)?
+        };
+        self.version = version.to_string();
+        self.query = match query {
+            Some(query) => parse_urlencoded(query)?,
+            None => vec![],
+        };
+
+        Ok(())
+    }
+
+    fn parse_header(&mut self, line: &str) -> ParseResult<()> {
+        if self.headers.len() >= self.limits.max_headers {
+            return Err(Box::new(limits::HeadersTooLargeError));
         }
 
-        uri.push(unchecked_uri[i].to_string());
+        // The key-value pair is delimited by the first colon char, as values
+        // like Host: a:7878 can have more of them
+        let Some((name, value)) = line.split_once(":") else {
+            return Err(Box::new(parse::ParseUriError));
+        };
+
+        // No whitespace is allowed between the name and the colon
+        if name.is_empty() || !name.bytes().all(is_token_char) {
+            return Err(Box::new(parse::ParseUriError));
+        }
+
+        // Repeated headers keep all of their values
+        self.headers.append(name.to_string(), value.trim().to_string());
+        Ok(())
     }
 
-    // If the vector is empty, we know the request is for the root URI
-    // This guarantees the vector's size is at least 1
-    if uri.len() == 0 {
-        uri.push("/".to_string());
+    // Decides how the body is framed once all headers are in. The transfer
+    // encoding takes precedence over the content length. We only support the
+    // chunked one, as it's the only one required for HTTP/1.1
+    fn body_state(&self) -> ParseResult<State> {
+        if self.headers.contains("Transfer-Encoding") {
+            let codings: Vec<String> = self.headers.get_all("Transfer-Encoding").iter()
+                .flat_map(|value| value.split(","))
+                .map(|coding| coding.trim().to_ascii_lowercase())
+                .collect();
+
+            if codings != ["chunked"] {
+                return Err(Box::new(implement::ImplementationError));
+            }
+
+            return Ok(State::ChunkSize);
+        }
+
+        let lengths = self.headers.get_all("Content-Length");
+
+        // If the header is missing, there's no body
+        let Some(content_length) = lengths.first() else {
+            return Ok(State::Done);
+        };
+
+        // Repeated lengths are only valid if they all agree
+        if lengths.iter().any(|length| length != content_length) {
+            return Err(Box::new(parse::ParseUriError));
+        }
+
+        let content_length = content_length.trim();
+
+        if content_length.is_empty() || !content_length.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(Box::new(parse::ParseUriError));
+        }
+
+        // We reject the body before reading it if it's too large. Lengths too
+        // big to parse are too large for sure
+        let content_length = content_length.parse::<usize>().unwrap_or(usize::MAX);
+
+        if content_length > self.limits.max_body_size {
+            return Err(Box::new(limits::PayloadTooLargeError));
+        }
+
+        if content_length == 0 {
+            return Ok(State::Done);
+        }
+
+        Ok(State::Body(content_length))
     }
 
-    let method = request_line[0].to_string();
-    let version = request_line[2].to_string();
-    let params = if uri_line.len() > 1 {
-        parse_urlencoded(uri_line[1].to_string())
-    } else {
-        HashMap::new()
-    };
+    // Builds the request and leaves the parser ready for the next one
+    fn finish(&mut self) -> ParseResult<HttpRequest> {
+        let headers = std::mem::take(&mut self.headers);
+        let content = std::mem::take(&mut self.content);
+        let query = std::mem::take(&mut self.query);
+        let method = std::mem::take(&mut self.method);
+        let uri = std::mem::take(&mut self.uri);
+        let version = std::mem::take(&mut self.version);
+        self.headers_size = 0;
+        self.state = State::RequestLine;
+
+        let body = parse_body(&headers, content)?;
+
+        // Repeated keys keep their first value in the map, the rest are still
+        // available through the ordered query pairs
+        let mut params: HashMap<String, String> = HashMap::new();
+
+        for (key, value) in &query {
+            params.entry(key.clone()).or_insert(value.clone());
+        }
+
+        let mut req = HttpRequest::new(method, uri, params, version, headers, body);
+        req.query = query;
 
-    Ok((method, uri, params, version))
+        Ok(req)
+    }
 }
 
-async fn parse_headers(buf_reader: &mut BufReader<&mut TcpStream>) -> HashMap<String, String> {
-    let mut headers: HashMap<String, String> = HashMap::new();
+// This function reads from the stream until the parser has a complete request
+// and returns it, or throws different errors to the caller. The reader is
+// borrowed, so the same connection can be used for the requests that follow.
+// The head and body have their own deadlines, given by the parser's limits
+pub async fn parse<R: AsyncRead + Unpin>(reader: &mut R, parser: &mut RequestParser) -> ParseResult<HttpRequest> {
+    let headers_deadline = Instant::now() + parser.limits().headers_timeout;
+    let mut body_deadline: Option<Instant> = None;
+    let mut buf = vec![0u8; 4096];
 
-    // Each header is separated by a new line (CRLF)
     loop {
-        let mut line = String::new();
-        let _ = buf_reader.read_line(&mut line).await;
+        if let Some(req) = parser.advance()? {
+            return Ok(req);
+        }
+
+        let deadline = if parser.in_body() {
+            *body_deadline.get_or_insert(Instant::now() + parser.limits().body_timeout)
+        } else {
+            headers_deadline
+        };
+
+        let Ok(read) = timeout_at(deadline, reader.read(&mut buf)).await else {
+            return Err(Box::new(limits::RequestTimeoutError));
+        };
+
+        let read = read?;
+
+        // The client hung up before completing the request
+        if read == 0 {
+            return Err(Box::new(parse::ParseUriError));
+        }
+
+        parser.push(&buf[..read]);
+    }
+}
 
-        // The headers and body are separated by an empty line (CRLF)
-        if line.len() == 2 {
-            break
+// Splits the path in its segments, decoding each one. The root path is
+// represented by a single "/" segment
+fn parse_path(path: &str) -> ParseResult<Vec<String>> {
+    // Only absolute paths are supported as request targets
+    let Some(path) = path.strip_prefix("/") else {
+        return Err(Box::new(parse::ParseUriError));
+    };
+
+    // A single trailing slash is allowed and ignored
+    let path = path.strip_suffix("/").unwrap_or(path);
+
+    if path.is_empty() {
+        return Ok(vec!["/".to_string()]);
+    }
+
+    let mut uri: Vec<String> = vec![];
+
+    // If there are empty segments, it means there were multiple consecutive
+    // slashes. Thus, it's an invalid URI and we return early with an error
+    for segment in path.split("/") {
+        if segment.is_empty() {
+            return Err(Box::new(parse::ParseUriError));
         }
 
-        // The key-value pair is delimited by a colon char
-        let header: Vec<&str> = line.split(":").collect();
-        headers.insert(header[0].to_string(), header[1].trim().to_string());
+        uri.push(percent_decode(segment, false)?);
     }
 
-    headers
+    Ok(uri)
 }
 
-async fn parse_body(buf_reader: &mut BufReader<&mut TcpStream>, headers: &HashMap<String, String>) -> Result<Body, Box<dyn std::error::Error + Send + Sync>> {
-    let mut body = Body::JSON(String::new());
+// Chunk extensions can follow the size after a ';' char, we ignore them
+fn parse_chunk_size(line: &str) -> ParseResult<usize> {
+    let size = line.split(";").next().unwrap_or("").trim();
 
-    // If the header is missing, we return early with an empty hashmap
-    let Some(content_length) = headers.get("Content-Length") else {
-        return Ok(body);
+    if size.is_empty() || !size.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Box::new(parse::ParseUriError));
+    }
+
+    // Sizes too big to parse are too large for sure
+    let Ok(size) = usize::from_str_radix(size, 16) else {
+        return Err(Box::new(limits::PayloadTooLargeError));
     };
 
-    let content_length = content_length.parse::<u64>()?;
+    Ok(size)
+}
+
+// Characters allowed in methods and header names (RFC 9110 tokens)
+fn is_token_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+fn parse_body(headers: &Headers, content: Vec<u8>) -> ParseResult<Body> {
+    let mut body = Body::JSON(String::new());
 
     // If the content is 0-length'd, we can stop the function
-    if content_length == 0 {
+    if content.is_empty() {
         return Ok(body);
     }
 
@@ -126,41 +474,117 @@ async fn parse_body(buf_reader: &mut BufReader<&mut TcpStream>, headers: &HashMa
         None => return Err(Box::new(parse::ParseUriError)),
     };
 
+    // Parameters like the charset come after the media type, split by ';'
+    let content_type = content_type.split(";").next().unwrap_or("").trim();
+
+    // Only the types we read as text are decoded, the rest are kept as bytes
     if content_type != "application/x-www-form-urlencoded" && content_type != "application/json" {
-        return Err(Box::new(implement::ImplementationError));
+        return Ok(Body::Binary(content));
     }
-    
-    // We read the indicated amount of bytes from the stream
-    let mut content: Vec<u8> = vec![];
-    let mut buffer_to_read = buf_reader.take(content_length);
-    let _ = buffer_to_read.read_to_end(&mut content).await;
-    let content = String::from_utf8(content)?;
+
+    let Ok(content) = String::from_utf8(content) else {
+        return Err(Box::new(parse::ParseUriError));
+    };
 
     if content_type == "application/json" {
         // We don't attempt to parse it, as we only need to check whether the
         // JSON is mal-formed or not
-        if let Err(_) = serde_json::from_str::<Value>(&content) {
+        if serde_json::from_str::<Value>(&content).is_err() {
             return Err(Box::new(parse::ParseUriError));
         }
 
         body = Body::JSON(content);
     } else {
-        body = Body::URLdec(());        
+        body = Body::URLdec(parse_urlencoded(&content)?);
     }
 
     Ok(body)
 }
 
-// Parser would work for query params and body as x-www-form-urlencoded
-fn parse_urlencoded(content: String) -> HashMap<String, String> {
-    let mut parsed: HashMap<String, String> = HashMap::new();
+// Parser would work for query params and body as x-www-form-urlencoded. The
+// pairs are returned in the order received, so repeated keys are kept
+pub fn parse_urlencoded(content: &str) -> Result<Vec<(String, String)>, parse::ParseUriError> {
+    let mut parsed: Vec<(String, String)> = vec![];
 
     // This encoding has key-value pairs joined by &
     for pair in content.split("&") {
-        // The key-value pair is delimited by an equal char
-        let pair: Vec<&str> = pair.split("=").collect();
-        parsed.insert(pair[0].to_string(), pair[1].to_string());
+        // Consecutive or trailing & chars leave empty pairs we can ignore
+        if pair.is_empty() {
+            continue;
+        }
+
+        // The key-value pair is delimited by the first equal char. A key
+        // without one (?flag) is valid and gets an empty value
+        let (key, value) = pair.split_once("=").unwrap_or((pair, ""));
+
+        if key.is_empty() {
+            return Err(parse::ParseUriError);
+        }
+
+        parsed.push((percent_decode(key, true)?, percent_decode(value, true)?));
     }
 
-    parsed
+    Ok(parsed)
+}
+
+// Decodes the %XX escapes of RFC 3986. The form encoding also uses '+' for
+// spaces, which is only applied when requested. As the escapes can split
+// multi-byte characters, we decode to bytes first and validate UTF-8 at the end
+pub fn percent_decode(content: &str, plus_as_space: bool) -> Result<String, parse::ParseUriError> {
+    let bytes = content.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                // Both chars after the % must be hex digits, otherwise the
+                // escape is mal-formed
+                let Some(hex) = bytes.get(i + 1..i + 3) else {
+                    return Err(parse::ParseUriError);
+                };
+
+                if !hex.iter().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(parse::ParseUriError);
+                }
+
+                let hex = std::str::from_utf8(hex).map_err(|_| parse::ParseUriError)?;
+                let byte = u8::from_str_radix(hex, 16).map_err(|_| parse::ParseUriError)?;
+
+                decoded.push(byte);
+                i += 3;
+            },
+            b'+' if plus_as_space => {
+                decoded.push(b' ');
+                i += 1;
+            },
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            },
+        }
+    }
+
+    String::from_utf8(decoded).map_err(|_| parse::ParseUriError)
+}
+
+// Inverse of the decoding, used when forwarding requests. Only the unreserved
+// characters of RFC 3986 are left as they are
+pub fn percent_encode(content: &str) -> String {
+    let mut encoded = String::with_capacity(content.len());
+
+    for byte in content.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded += &format!("%{:02X}", byte);
+        }
+    }
+
+    encoded
+}
+
+// Position of the first occurrence of the needle, used to find delimiters
+pub fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
 }
@@ -0,0 +1,76 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::models::request::HttpRequest;
+
+pub const HEADER: &str = "X-Request-Id";
+
+// Longer IDs are replaced, so clients can't flood the logs
+const MAX_LENGTH: usize = 128;
+
+// The request being answered by the current task
+#[derive(Debug)]
+struct RequestContext {
+    id: String,
+    // Requests sent to slaves on its behalf, used for their sub-IDs
+    sent: AtomicUsize,
+}
+
+tokio::task_local! {
+    static CURRENT: Arc<RequestContext>;
+}
+
+// The ID sent by the client or a proxy, or a new one if it has none or it
+// isn't printable
+pub fn from_request(req: &HttpRequest) -> String {
+    req.headers.get(HEADER)
+        .map(|id| id.trim())
+        .filter(|id| !id.is_empty() && id.len() <= MAX_LENGTH && id.chars().all(|c| c.is_ascii_graphic()))
+        .map(|id| id.to_string())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+// Runs the future as part of the given request, so logs and the requests sent
+// to slaves can use its ID
+pub async fn scope<F: Future>(id: String, future: F) -> F::Output {
+    let context = Arc::new(RequestContext { id, sent: AtomicUsize::new(0) });
+    CURRENT.scope(context, future).await
+}
+
+// Spawned tasks don't inherit the request, so futures that will be spawned
+// need to take it with them
+pub fn propagate<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let context = CURRENT.try_with(Arc::clone).ok();
+
+    async move {
+        match context {
+            Some(context) => CURRENT.scope(context, future).await,
+            None => future.await,
+        }
+    }
+}
+
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|context| context.id.clone()).ok()
+}
+
+// Forwarded requests keep the client's ID, as the slave answers the same
+// request. The ones created by the master get a sub-ID
+pub fn tag_forwarded(req: &mut HttpRequest) {
+    if !req.headers.contains(HEADER) {
+        tag_partial(req);
+    }
+}
+
+// Partial tasks get the current ID followed by their number, so each one can
+// be told apart in the slaves' logs
+pub fn tag_partial(req: &mut HttpRequest) {
+    let sub_id = CURRENT.try_with(|context| {
+        format!("{}.{}", context.id, context.sent.fetch_add(1, Ordering::Relaxed) + 1)
+    });
+
+    if let Ok(sub_id) = sub_id {
+        req.headers.insert(HEADER.to_string(), sub_id);
+    }
+}
@@ -0,0 +1,295 @@
+use std::any::{type_name, Any};
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::str::FromStr;
+
+use crate::errors::log_error;
+use crate::models::request::HttpRequest;
+use crate::models::response::HttpResponse;
+
+use super::methods;
+
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
+
+type Value = Box<dyn Any + Send + Sync>;
+
+// Handlers get the request along with its validated parameters. Async ones
+// are meant for work that waits on other nodes
+pub enum Handler<T> {
+    Sync(fn(HttpRequest, Context) -> T),
+    Async(fn(HttpRequest, Context) -> BoxFuture<T>),
+}
+
+// A parameter a route reads with HttpRequest::param, so it can come from the
+// query or a form body
+struct Param {
+    name: &'static str,
+    // Short name of the type, shown in /help
+    kind: &'static str,
+    parse: fn(&str) -> Option<Value>,
+}
+
+fn parse_value<P: FromStr + Send + Sync + 'static>(value: &str) -> Option<Value> {
+    value.parse::<P>().ok().map(|value| Box::new(value) as Value)
+}
+
+// What the router gives handlers besides the request
+pub struct Context {
+    pub remote: SocketAddr,
+    captures: HashMap<&'static str, String>,
+    values: HashMap<&'static str, Value>,
+}
+
+impl Context {
+    // Path segment matched by a {name} capture of the route's template.
+    // Panics if the template doesn't have it, as with parameters
+    pub fn capture(&self, name: &str) -> &str {
+        self.captures.get(name)
+            .unwrap_or_else(|| panic!("Capture {} isn't part of the route", name))
+    }
+
+    // Declared parameters are always there, with their type. Asking for
+    // anything else is a bug in the route, so it panics
+    pub fn get<P: 'static>(&self, name: &str) -> &P {
+        self.values.get(name)
+            .and_then(|value| value.downcast_ref::<P>())
+            .unwrap_or_else(|| panic!("Parameter {} wasn't declared as {}", name, type_name::<P>()))
+    }
+}
+
+// Part of a route's template. Captures, written as {name}, match any segment
+enum Segment {
+    Literal(&'static str),
+    Capture(&'static str),
+}
+
+pub struct Route<T> {
+    method: &'static str,
+    path: &'static str,
+    segments: Vec<Segment>,
+    params: Vec<Param>,
+    handler: Handler<T>,
+    hidden: bool,
+}
+
+impl<T> Route<T> {
+    pub fn new(method: &'static str, path: &'static str, handler: Handler<T>) -> Route<T> {
+        let segments = path.split("/")
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| match segment.strip_prefix("{").and_then(|segment| segment.strip_suffix("}")) {
+                Some(name) => Segment::Capture(name),
+                None => Segment::Literal(segment),
+            })
+            .collect();
+        Route { method, path, segments, params: vec![], handler, hidden: false }
+    }
+
+    pub fn sync(method: &'static str, path: &'static str, handler: fn(HttpRequest, Context) -> T) -> Route<T> {
+        Self::new(method, path, Handler::Sync(handler))
+    }
+
+    pub fn asynchronous(method: &'static str, path: &'static str, handler: fn(HttpRequest, Context) -> BoxFuture<T>) -> Route<T> {
+        Self::new(method, path, Handler::Async(handler))
+    }
+
+    // Requests missing the parameter, or with a value that can't be parsed as
+    // the given type, are answered with a 400
+    pub fn param<P: FromStr + Send + Sync + 'static>(mut self, name: &'static str) -> Route<T> {
+        let kind = type_name::<P>().rsplit("::").next().unwrap_or("");
+        self.params.push(Param { name, kind, parse: parse_value::<P> });
+        self
+    }
+
+    // Routes used between nodes aren't listed in /help
+    pub fn hidden(mut self) -> Route<T> {
+        self.hidden = true;
+        self
+    }
+
+    // Returns the captured segments if the whole path matches the template.
+    // The root path is the only one matched by an empty template
+    fn matches(&self, uri: &[String]) -> Option<HashMap<&'static str, String>> {
+        if self.segments.is_empty() {
+            return (uri == ["/"]).then(HashMap::new);
+        }
+
+        if uri.len() != self.segments.len() {
+            return None;
+        }
+
+        let mut captures = HashMap::new();
+
+        for (segment, part) in self.segments.iter().zip(uri) {
+            match segment {
+                Segment::Literal(literal) if literal == part => {},
+                Segment::Literal(_) => return None,
+                Segment::Capture(name) => {
+                    captures.insert(*name, part.clone());
+                },
+            }
+        }
+
+        Some(captures)
+    }
+
+    // Templates with more literal segments are more specific, so /jobs/latest
+    // is chosen over /jobs/{id}
+    fn literals(&self) -> usize {
+        self.segments.iter().filter(|segment| matches!(segment, Segment::Literal(_))).count()
+    }
+
+    fn parse_params(&self, req: &HttpRequest) -> Result<HashMap<&'static str, Value>, HttpResponse> {
+        let mut values = HashMap::new();
+
+        for param in &self.params {
+            let Some(value) = req.param(param.name) else {
+                return Err(invalid_param(format!("Missing parameter: {}", param.name)));
+            };
+
+            let Some(value) = (param.parse)(value) else {
+                return Err(invalid_param(format!("Unable to parse {} param!", param.name)));
+            };
+
+            values.insert(param.name, value);
+        }
+
+        Ok(values)
+    }
+
+    // Line shown in /help, like GET /reverse?text=<String>
+    fn usage(&self) -> String {
+        let mut usage = format!("{} {}", self.method, self.path);
+
+        for (index, param) in self.params.iter().enumerate() {
+            let separator = if index == 0 { "?" } else { "&" };
+            usage += &format!("{}{}=<{}>", separator, param.name, param.kind);
+        }
+
+        usage
+    }
+}
+
+// Methods of the given routes, in the order they were registered
+fn allowed<'a, T: 'a>(routes: impl Iterator<Item = &'a Route<T>>) -> Vec<&'static str> {
+    let mut allowed = vec![];
+
+    for route in routes {
+        if !allowed.contains(&route.method) {
+            allowed.push(route.method);
+        }
+    }
+
+    allowed
+}
+
+fn invalid_param(contents: String) -> HttpResponse {
+    HttpResponse::text(400, contents)
+}
+
+// Routes requests to the handler registered for their method and path. The
+// allowed methods of a path come from its routes, so OPTIONS and 405s are
+// answered here too
+pub struct Router<T> {
+    routes: Vec<Route<T>>,
+    // Called once a request is about to reach its handler
+    on_dispatch: Option<fn(&HttpRequest)>,
+}
+
+impl<T> Default for Router<T> {
+    fn default() -> Self {
+        Router { routes: vec![], on_dispatch: None }
+    }
+}
+
+impl<T> Router<T> {
+    pub fn new() -> Router<T> {
+        Router::default()
+    }
+
+    pub fn route(mut self, route: Route<T>) -> Router<T> {
+        self.routes.push(route);
+        self
+    }
+
+    pub fn on_dispatch(mut self, hook: fn(&HttpRequest)) -> Router<T> {
+        self.on_dispatch = Some(hook);
+        self
+    }
+
+    // Finds the handler for the request and checks its parameters. Requests
+    // that can't reach any get the response to send instead
+    fn resolve(&self, req: &HttpRequest, remote: SocketAddr) -> Result<(&Handler<T>, Context), HttpResponse> {
+        // OPTIONS * asks for what the whole server allows
+        if req.is_asterisk() {
+            let mut res = HttpResponse::basic(204);
+            res.headers.insert("Allow".to_string(), methods::allow(&allowed(self.routes.iter())));
+            return Err(res);
+        }
+
+        let mut routes: Vec<(&Route<T>, HashMap<&'static str, String>)> = self.routes.iter()
+            .filter_map(|route| Some((route, route.matches(&req.uri)?)))
+            .collect();
+
+        let Some(literals) = routes.iter().map(|(route, _)| route.literals()).max() else {
+            return Err(HttpResponse::basic(404));
+        };
+
+        // Only the most specific template is used
+        routes.retain(|(route, _)| route.literals() == literals);
+
+        let allowed = allowed(routes.iter().map(|(route, _)| *route));
+
+        if let Some(res) = methods::check(&req.method, &allowed) {
+            return Err(res);
+        }
+
+        let Some((route, captures)) = routes.into_iter().find(|(route, _)| route.method == req.method) else {
+            return Err(HttpResponse::basic(405));
+        };
+
+        let values = route.parse_params(req)?;
+
+        if let Some(hook) = self.on_dispatch {
+            hook(req);
+        }
+
+        Ok((&route.handler, Context { remote, captures, values }))
+    }
+
+    // For callers that can't await. Routers used this way should only have
+    // sync handlers
+    pub fn handle_sync(&self, req: HttpRequest, remote: SocketAddr) -> Result<T, HttpResponse> {
+        let (handler, ctx) = self.resolve(&req, remote)?;
+
+        match handler {
+            Handler::Sync(handler) => Ok(handler(req, ctx)),
+            Handler::Async(_) => {
+                log_error(format!("Route {} can't be handled synchronously", req.uri.join("/")).into());
+                Err(HttpResponse::basic(500))
+            },
+        }
+    }
+
+    pub async fn handle(&self, req: HttpRequest, remote: SocketAddr) -> Result<T, HttpResponse> {
+        let (handler, ctx) = self.resolve(&req, remote)?;
+
+        match handler {
+            Handler::Sync(handler) => Ok(handler(req, ctx)),
+            Handler::Async(handler) => Ok(handler(req, ctx).await),
+        }
+    }
+
+    // Lists the visible routes with their parameters
+    pub fn help(&self) -> String {
+        let mut help = "\nAvailable Commands:\n".to_string();
+
+        for route in self.routes.iter().filter(|route| !route.hidden) {
+            help += &route.usage();
+            help += "\n";
+        }
+
+        help
+    }
+}
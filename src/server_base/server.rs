@@ -1,16 +1,33 @@
 use std::net::{SocketAddr, ToSocketAddrs};
-use tokio::{net::{TcpListener, TcpStream}};
-use tokio::io::AsyncWriteExt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::Receiver;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
 
 use crate::client::client;
 use crate::errors::{self, *};
 use crate::models::request::HttpRequest;
-use crate::models::response::{HttpResponse, Response};
+use crate::models::response::{self, HttpResponse, Response};
 use crate::status::status;
 use crate::server_master;
 use crate::server_slave;
 
-use super::parser::parse;
+use super::admission::{self, Admission};
+use super::compression::{self, Encoding, StreamCompressor};
+use super::cors;
+use super::request_id;
+use super::limits::Limits;
+use super::shutdown;
+use super::tls;
+use super::parser::{parse, RequestParser};
+
+const HTTP_1_0: &str = "HTTP/1.0";
+const HTTP_1_1: &str = "HTTP/1.1";
 
 pub async fn create_server(port: u16, role: String) {
     let address = SocketAddr::from(([0, 0, 0, 0], port));
@@ -23,70 +40,438 @@ pub async fn create_server(port: u16, role: String) {
         },
     };
 
+    // Slaves already did it before reporting to the master
+    status::update_main_pid(gettid::gettid());
+
+    let limits = Limits::from_env(&role);
+    serve(listener, role, Arc::new(limits), tls::acceptor_from_env(), shutdown::token()).await;
+}
+
+// Accepts connections until the token is cancelled. Then, the listener is
+// closed and in-flight requests get a grace period to finish before their
+// tasks are aborted. With an acceptor, every connection must use TLS
+pub(super) async fn serve(listener: TcpListener, role: String, limits: Arc<Limits>, acceptor: Option<TlsAcceptor>, token: CancellationToken) {
+    let mut connections = JoinSet::new();
+    let admission = Admission::new(&limits);
+
     loop {
-        let stream = listener.accept().await;
+        let stream = tokio::select! {
+            _ = token.cancelled() => break,
+            stream = listener.accept() => stream,
+        };
+
         let role = role.clone();
+        let limits = Arc::clone(&limits);
+        let token = token.clone();
+        let acceptor = acceptor.clone();
 
         if let Ok((stream, remote)) = stream {
-            tokio::spawn(async move {
-                let _ = handle_requests(stream, remote, role).await;
-            });
+            if let Some(permit) = admission.connection() {
+                let admission = admission.clone();
+
+                connections.spawn(async move {
+                    let _permit = permit;
+
+                    let Some(acceptor) = acceptor else {
+                        handle_requests(stream, remote, role, limits, admission, token).await;
+                        return;
+                    };
+
+                    // The handshake is bounded like the request head, so idle
+                    // clients can't hold the connection
+                    match timeout(limits.headers_timeout, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            handle_requests(stream, remote, role, limits, admission, token).await;
+                        },
+                        Ok(Err(e)) => log_error(Box::new(e)),
+                        Err(_) => {},
+                    }
+                });
+            } else if let Some(rejection) = admission.rejection() {
+                // Connections over the cap get a 503 without reading their
+                // request, so clients know to retry instead of seeing a reset
+                connections.spawn(async move {
+                    let _rejection = rejection;
+                    reject(stream, acceptor, limits).await;
+                });
+            } else {
+                // Too many are being answered already, so the rest are reset
+                let _ = stream.set_linger(Some(Duration::ZERO));
+            }
         }
+
+        // We drop the finished connections, so the set doesn't keep growing
+        while connections.try_join_next().is_some() {}
     }
+
+    drop(listener);
+
+    let drained = timeout(limits.shutdown_grace, async {
+        while connections.join_next().await.is_some() {}
+    }).await;
+
+    if drained.is_err() {
+        log_error(format!("Grace period over, aborting {} connections", connections.len()).into());
+        connections.shutdown().await;
+    }
+
+    log_info("Server stopped".to_string());
 }
 
-async fn handle_requests(req: TcpStream, remote: SocketAddr, role: String) -> Result<(), Box<dyn std::error::Error>> {
-    // We duplicate the stream as tokio doesn't have a clone fn or copy trait
-    let req_std = req.into_std()?;
-    let parsing_req = TcpStream::from_std(req_std.try_clone()?)?;
-    let req = TcpStream::from_std(req_std)?;
+// Each connection can carry multiple requests (HTTP/1.1 persistent
+// connections). Requests are handled one at a time, so pipelined requests get
+// their responses in the same order they were sent
+pub(super) async fn handle_requests<S: AsyncRead + AsyncWrite + Unpin>(req: S, remote: SocketAddr, role: String, limits: Arc<Limits>, admission: Admission, token: CancellationToken) {
+    let (mut reader, mut writer) = tokio::io::split(req);
 
-    let version = "HTTP/1.1".to_string();
-    let message = parse(parsing_req).await;
+    // Errors here mean the client is gone, so there's no one to answer
+    let _ = answer_requests(&mut reader, &mut writer, remote, role, limits, admission, token).await;
 
-    let message = match message {
-        Ok(message) => message,
-        // Error handling based on error type
-        Err(e) => {
-            if e.is::<errors::parse::ParseUriError>() {
-            let res = HttpResponse::basic(400);
-                return send_response(req, res).await;
+    // Closing our side lets TLS clients know the connection ended on purpose
+    let _ = writer.shutdown().await;
+}
+
+// Answers a connection that wasn't admitted with a fixed 503, without parsing
+// anything. Everything is bounded like the request head, so idle clients
+// can't hold the slot
+async fn reject(stream: TcpStream, acceptor: Option<TlsAcceptor>, limits: Arc<Limits>) {
+    let mut res = admission::unavailable(limits.retry_after);
+    res.headers.insert("Connection".to_string(), "close".to_string());
+    let message = res.to_bytes();
+
+    let _ = timeout(limits.headers_timeout, async {
+        match acceptor {
+            Some(acceptor) => close_with(acceptor.accept(stream).await?, &message).await,
+            None => close_with(stream, &message).await,
+        }
+    }).await;
+}
+
+// The request is read and dropped after answering, as closing with unread
+// data would reset the connection before the client reads the answer
+async fn close_with<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, message: &[u8]) -> std::io::Result<()> {
+    stream.write_all(message).await?;
+    stream.shutdown().await?;
+
+    let mut buf = [0u8; 1024];
+    while stream.read(&mut buf).await? > 0 {}
+
+    Ok(())
+}
+
+async fn answer_requests<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(reader: &mut R, writer: &mut W, remote: SocketAddr, role: String, limits: Arc<Limits>, admission: Admission, token: CancellationToken) -> Result<(), Box<dyn std::error::Error>> {
+    let mut parser = RequestParser::new(limits.as_ref().clone());
+    let mut buf = vec![0u8; 4096];
+
+    loop {
+        // Unless a pipelined request is already buffered, we wait for the
+        // next one to start arriving, closing the connection if the client
+        // stays idle for too long, hangs up or the server is shutting down
+        if !parser.has_buffered() {
+            // A request that already arrived is still answered
+            let read = tokio::select! {
+                biased;
+                read = timeout(limits.keep_alive_timeout, reader.read(&mut buf)) => read,
+                _ = token.cancelled() => return Ok(()),
+            };
+
+            match read {
+                Err(_) => return Ok(()),
+                Ok(Err(e)) => return Err(Box::new(e)),
+                Ok(Ok(0)) => return Ok(()),
+                Ok(Ok(read)) => parser.push(&buf[..read]),
             }
-        
-            if e.is::<errors::implement::ImplementationError>() {
-            let res = HttpResponse::basic(501);
-                return send_response(req, res).await;
+        }
+
+        let message = parse(reader, &mut parser).await;
+
+        let mut message = match message {
+            Ok(message) => message,
+            // Error handling based on error type. As we can't know where the
+            // next request would start, the connection is closed afterwards
+            Err(e) => {
+                let res = Response::HTTP(HttpResponse::basic(status_from_error(e)));
+                return send(writer, res, Reply::closing()).await;
+            },
+        };
+
+        if message.version != HTTP_1_0 && message.version != HTTP_1_1 {
+            let res = Response::HTTP(HttpResponse::basic(505));
+            return send(writer, res, Reply::closing()).await;
+        }
+
+        // Every request keeps or gets an ID, which is also sent to the slaves
+        // and answered to the client
+        let id = request_id::from_request(&message);
+        message.headers.insert(request_id::HEADER.to_string(), id.clone());
+
+        let version = message.version.clone();
+        let mut keep_alive = keep_alive(&message);
+
+        // HEAD requests are routed as GET ones, and only the head of the
+        // response is sent
+        let head_only = message.method == "HEAD";
+
+        if head_only {
+            message.method = "GET".to_string();
+        }
+
+        let encoding = compression::negotiate(&message.headers);
+        let origin = message.headers.get("Origin").cloned();
+        let cors = cors::policy();
+
+        // The permit is held until the response is sent, streams included
+        let Some(_permit) = admission.request() else {
+            let keep_alive = keep_alive && !token.is_cancelled();
+            let mut res = Response::HTTP(admission::unavailable(limits.retry_after));
+            res.head_mut().headers.insert(request_id::HEADER.to_string(), id);
+
+            if let Some(cors) = cors {
+                cors.decorate(origin.as_deref(), &mut res);
+            }
+
+            let reply = Reply { version, keep_alive, head_only, encoding: None, compression_min_size: limits.compression_min_size };
+
+            send(writer, res, reply).await?;
+
+            if !keep_alive {
+                return Ok(());
+            }
+
+            continue;
+        };
+
+        // Preflights are answered here, as they're the same for every route
+        let preflight = cors.and_then(|cors| cors.preflight(&message));
+
+        let mut res = request_id::scope(id.clone(), async {
+            if let Some(preflight) = preflight {
+                Response::HTTP(preflight)
+            } else if role == "MASTER" {
+                server_master::routes::handle_route(message, remote).await
+            } else {
+                Response::HTTP(server_slave::routes::handle_route(message, remote))
+            }
+        }).await;
+
+        res.head_mut().headers.insert(request_id::HEADER.to_string(), id);
+
+        if let Some(cors) = cors {
+            cors.decorate(origin.as_deref(), &mut res);
+        }
+
+        // HTTP/1.0 has no chunked encoding, so streams are delimited by
+        // closing the connection
+        if version == HTTP_1_0 && matches!(res, Response::Stream(..)) {
+            keep_alive = false;
+        }
+
+        // Clients are told to reconnect elsewhere while shutting down
+        if token.is_cancelled() {
+            keep_alive = false;
+        }
+
+        let reply = Reply {
+            version,
+            keep_alive,
+            head_only,
+            encoding,
+            compression_min_size: limits.compression_min_size,
+        };
+
+        send(writer, res, reply).await?;
+
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+// HTTP/1.1 connections are persistent unless the client asks to close them,
+// while HTTP/1.0 ones are closed unless the client asks to keep them
+fn keep_alive(req: &HttpRequest) -> bool {
+    if req.version == HTTP_1_0 {
+        req.headers.has_token("Connection", "keep-alive")
+    } else {
+        !req.headers.has_token("Connection", "close")
+    }
+}
+
+// Maps the errors the parser can throw to the status code answered
+fn status_from_error(e: Box<dyn std::error::Error + Send + Sync>) -> u16 {
+    if e.is::<errors::parse::ParseUriError>() {
+        400
+    } else if e.is::<errors::limits::RequestTimeoutError>() {
+        408
+    } else if e.is::<errors::limits::PayloadTooLargeError>() {
+        413
+    } else if e.is::<errors::limits::UriTooLongError>() {
+        414
+    } else if e.is::<errors::limits::HeadersTooLargeError>() {
+        431
+    } else if e.is::<errors::implement::ImplementationError>() {
+        501
+    } else {
+        500
+    }
+}
+
+// How a response is written, based on the request it answers
+struct Reply {
+    version: String,
+    keep_alive: bool,
+    // HEAD requests only get the head of the response
+    head_only: bool,
+    // Coding accepted by the client, if any
+    encoding: Option<Encoding>,
+    compression_min_size: usize,
+}
+
+impl Reply {
+    // Used for requests that can't be answered, as the connection can't be
+    // trusted afterwards
+    fn closing() -> Reply {
+        Reply {
+            version: HTTP_1_1.to_string(),
+            keep_alive: false,
+            head_only: false,
+            encoding: None,
+            compression_min_size: 0,
+        }
+    }
+}
+
+// The response's version matches the one of the request, so its semantics
+// apply to the whole exchange
+async fn send<W: AsyncWrite + Unpin>(writer: &mut W, res: Response, reply: Reply) -> Result<(), Box<dyn std::error::Error>> {
+    match res {
+        Response::HTTP(mut res) => {
+            prepare_head(&mut res, &reply);
+
+            // A buffered response can always be sent with its length instead
+            if reply.version == HTTP_1_0 {
+                res.headers.remove("Transfer-Encoding");
+            }
+
+            send_response(writer, res, &reply).await
+        },
+        Response::Stream(mut res, chunks) => {
+            prepare_head(&mut res, &reply);
+
+            // The stream's size is unknown, so it's always compressed
+            let compressor = reply.encoding.map(|encoding| {
+                res.headers.insert("Content-Encoding".to_string(), encoding.name().to_string());
+                StreamCompressor::new(encoding)
+            });
+            res.headers.add_token("Vary", "Accept-Encoding");
+
+            if reply.version == HTTP_1_0 {
+                send_stream_raw(writer, res, chunks, compressor, reply.head_only).await
             } else {
-            let res = HttpResponse::basic(500);
-                return send_response(req, res).await;
+                send_stream(writer, res, chunks, compressor, reply.head_only).await
             }
         },
-    };
-    
-    if message.version != version {
-        let res = HttpResponse::basic(505);
-            return send_response(req, res).await;
     }
+}
+
+fn prepare_head(res: &mut HttpResponse, reply: &Reply) {
+    res.version = reply.version.clone();
 
-    if role == "MASTER" {
-        match server_master::routes::handle_route(message, remote).await {
-            Response::HTTP(res) => send_response(req, res).await,
-            Response::Buffer(buffer) => send_buffer(req, buffer).await,
+    if !reply.keep_alive {
+        res.headers.insert("Connection".to_string(), "close".to_string());
+    } else if reply.version == HTTP_1_0 {
+        // HTTP/1.0 clients need to be told the connection stays open
+        res.headers.insert("Connection".to_string(), "keep-alive".to_string());
+    }
+}
+
+// Framing and the other standard headers are added by the response itself,
+// so compressing only needs to replace the body
+async fn send_response<W: AsyncWrite + Unpin>(writer: &mut W, mut res: HttpResponse, reply: &Reply) -> Result<(), Box<dyn std::error::Error>> {
+    // Bodies big enough to be compressed depend on the Accept-Encoding, which
+    // caches need to know. Partial bodies are left as they are, as their range
+    // refers to the uncompressed file
+    if res.contents.len() >= reply.compression_min_size && !res.contents.is_empty() && !res.headers.contains("Content-Encoding") && !res.headers.contains("Content-Range") {
+        res.headers.add_token("Vary", "Accept-Encoding");
+
+        if let Some(encoding) = reply.encoding {
+            res.contents = compression::compress(encoding, &res.contents)?;
+            res.headers.remove("Transfer-Encoding");
+            res.headers.insert("Content-Encoding".to_string(), encoding.name().to_string());
+
+            // The compressed bytes differ from the ones the tag was made for
+            if let Some(etag) = res.headers.get("ETag").filter(|etag| !etag.starts_with("W/")) {
+                let etag = format!("W/{}", etag);
+                res.headers.insert("ETag".to_string(), etag);
+            }
         }
-    } else {
-        let res = server_slave::routes::handle_route(message, remote);
-        
-        send_response(req, res).await
     }
 
+    // The head keeps the length the body would have
+    if reply.head_only {
+        return Ok(writer.write_all(res.head().as_bytes()).await?);
+    }
+
+    Ok(writer.write_all(&res.to_bytes()).await?)
 }
 
-async fn send_response(mut req: TcpStream, res: HttpResponse) -> Result<(), Box<dyn std::error::Error>> {
-    Ok(req.write_all(format!("{}", res).as_bytes()).await?)
+// Sends the head right away and then every chunk as it's produced, so long
+// results don't need to be buffered before answering
+async fn send_stream<W: AsyncWrite + Unpin>(writer: &mut W, mut res: HttpResponse, mut chunks: Receiver<Vec<u8>>, mut compressor: Option<StreamCompressor>, head_only: bool) -> Result<(), Box<dyn std::error::Error>> {
+    res.headers.remove("Content-Length");
+    res.headers.insert("Transfer-Encoding".to_string(), "chunked".to_string());
+    writer.write_all(res.head().as_bytes()).await?;
+
+    // Dropping the receiver lets the producer know it can stop
+    if head_only {
+        return Ok(());
+    }
+
+    while let Some(chunk) = chunks.recv().await {
+        let chunk = match &mut compressor {
+            Some(compressor) => compressor.compress(&chunk)?,
+            None => chunk,
+        };
+
+        // An empty chunk would be read as the end of the body
+        if chunk.is_empty() {
+            continue;
+        }
+
+        writer.write_all(&response::encode_chunk(&chunk)).await?;
+    }
+
+    if let Some(compressor) = compressor {
+        writer.write_all(&response::encode_chunk(&compressor.finish()?)).await?;
+    }
+
+    Ok(writer.write_all(response::LAST_CHUNK).await?)
 }
 
-async fn send_buffer(mut req: TcpStream, buffer: Vec<u8>) -> Result<(), Box<dyn std::error::Error>> {
-    Ok(req.write_all(&buffer).await?)
+// Without chunked encoding, the data is written as it comes and the end of the
+// body is marked by closing the connection
+async fn send_stream_raw<W: AsyncWrite + Unpin>(writer: &mut W, mut res: HttpResponse, mut chunks: Receiver<Vec<u8>>, mut compressor: Option<StreamCompressor>, head_only: bool) -> Result<(), Box<dyn std::error::Error>> {
+    res.headers.remove("Transfer-Encoding");
+    writer.write_all(res.head_until_close().as_bytes()).await?;
+
+    if head_only {
+        return Ok(());
+    }
+
+    while let Some(chunk) = chunks.recv().await {
+        let chunk = match &mut compressor {
+            Some(compressor) => compressor.compress(&chunk)?,
+            None => chunk,
+        };
+
+        writer.write_all(&chunk).await?;
+    }
+
+    if let Some(compressor) = compressor {
+        writer.write_all(&compressor.finish()?).await?;
+    }
+
+    Ok(())
 }
 
 pub fn report_to_master(port: u16, master_socket: String, slave_code: String) {
@@ -100,6 +485,13 @@ pub fn report_to_master(port: u16, master_socket: String, slave_code: String) {
         // AS the master could take some time to initialize, we wait some time once
         std::thread::sleep(std::time::Duration::from_secs(10));
         loop {
+            // Once shutting down, we stop reporting so the master removes
+            // this slave and stops sending it work
+            if shutdown::is_requested() {
+                log_info("Stopping heartbeat to master".to_string());
+                return;
+            }
+
             // We get only the first entry as there should be only 1 DNS result 
             let master_socket = master_socket.to_socket_addrs().unwrap().next().unwrap();
         
@@ -107,22 +499,17 @@ pub fn report_to_master(port: u16, master_socket: String, slave_code: String) {
             req.method = "POST".to_string();
             req.params.insert("port".to_string(), port.to_string());
             req.params.insert("slave_code".to_string(), slave_code.clone());
+            req.params.insert("busy".to_string(), status::busy_threads().to_string());
             req.uri.push("slave".to_string());
             req.version = "HTTP/1.1".to_string();
         
-            let master_res = match client::send_sync_request(master_socket, req) {
-                Ok(res) => res,
-                Err(_) => {
-                    log_error("Master unreachable!".into());
-                    panic!("Unrecoverable error! Check logs.");
-                },
-            };
-        
-            if master_res.len() == 0 {
+            // Any complete response means the master is up, even if it
+            // rejected the heartbeat
+            if client::send_sync_request(master_socket, req).is_err() {
                 log_error("Master unreachable!".into());
                 panic!("Unrecoverable error! Check logs.");
             }
-        
+
             std::thread::sleep(std::time::Duration::from_secs(4));
         }
     });
@@ -0,0 +1,45 @@
+use std::sync::LazyLock;
+use tokio_util::sync::CancellationToken;
+
+use crate::errors::{log_error, log_info};
+
+// Cancelled once the process is asked to stop. The server stops accepting
+// connections and the slave's heartbeat stops reporting to the master
+static SHUTDOWN: LazyLock<CancellationToken> = LazyLock::new(CancellationToken::new);
+
+pub fn token() -> CancellationToken {
+    SHUTDOWN.clone()
+}
+
+pub fn is_requested() -> bool {
+    SHUTDOWN.is_cancelled()
+}
+
+// Waits for SIGTERM, sent by k8s when terminating a pod, or SIGINT (Ctrl+C)
+// and then starts the shutdown
+pub async fn listen() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = terminate.recv() => {},
+                    _ = tokio::signal::ctrl_c() => {},
+                }
+            },
+            // We can still be stopped with SIGINT
+            Err(e) => {
+                log_error(Box::new(e));
+                let _ = tokio::signal::ctrl_c().await;
+            },
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+
+    log_info("Shutdown requested, draining in-flight requests".to_string());
+    SHUTDOWN.cancel();
+}
@@ -0,0 +1,158 @@
+use std::ops::Range;
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+
+use crate::models::headers::Headers;
+use crate::models::request::HttpRequest;
+use crate::models::response::{http_date, HttpResponse};
+
+// Type sent for a file, based on its extension
+pub fn content_type(name: &str) -> &'static str {
+    let extension = name.rsplit_once(".").map(|(_, extension)| extension.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("csv") => "text/csv; charset=utf-8",
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("json") => "application/json",
+        Some("pdf") => "application/pdf",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+// Strong validator for the content, so any change gives a different one
+pub fn etag(content: &[u8]) -> String {
+    format!("\"{}\"", sha256::digest(content))
+}
+
+// Answers a GET for a file, honouring the conditional headers and a single
+// byte range. Modification times are only known for files on disk
+pub fn serve(req: &HttpRequest, content: Vec<u8>, modified: Option<SystemTime>, content_type: &str) -> HttpResponse {
+    let etag = etag(&content);
+    // Dates in headers only have seconds
+    let modified = modified
+        .and_then(|modified| DateTime::<Utc>::from_timestamp(DateTime::<Utc>::from(modified).timestamp(), 0));
+
+    let mut headers = Headers::new();
+    headers.insert("ETag".to_string(), etag.clone());
+    headers.insert("Accept-Ranges".to_string(), "bytes".to_string());
+
+    if let Some(modified) = modified {
+        headers.insert("Last-Modified".to_string(), http_date(modified));
+    }
+
+    if not_modified(&req.headers, &etag, modified) {
+        return HttpResponse::new("HTTP/1.1".to_string(), 304, headers, vec![]);
+    }
+
+    headers.insert("Content-Type".to_string(), content_type.to_string());
+    let total = content.len();
+
+    // Ranges only apply if the client still has the same version
+    let range = req.headers.get("Range").filter(|_| if_range_matches(&req.headers, &etag));
+
+    match range.map(|range| byte_range(range, total)) {
+        Some(Ok(Some(range))) => {
+            headers.insert("Content-Range".to_string(), format!("bytes {}-{}/{}", range.start, range.end - 1, total));
+            HttpResponse::new("HTTP/1.1".to_string(), 206, headers, content[range].to_vec())
+        },
+        Some(Err(())) => {
+            headers.insert("Content-Range".to_string(), format!("bytes */{}", total));
+            headers.remove("Content-Type");
+            HttpResponse::new("HTTP/1.1".to_string(), 416, headers, vec![])
+        },
+        // Invalid or multiple ranges are ignored, sending the whole file
+        _ => HttpResponse::new("HTTP/1.1".to_string(), 200, headers, content),
+    }
+}
+
+// If-None-Match takes precedence, as ETags are more precise than dates
+fn not_modified(headers: &Headers, etag: &str, modified: Option<DateTime<Utc>>) -> bool {
+    let tags = headers.get_all("If-None-Match");
+
+    if !tags.is_empty() {
+        return tags.iter()
+            .flat_map(|value| value.split(","))
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || weak_match(tag, etag));
+    }
+
+    let since = headers.get("If-Modified-Since")
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok());
+
+    match (since, modified) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false,
+    }
+}
+
+// The W/ prefix is ignored, as compressed responses get weak tags
+fn weak_match(a: &str, b: &str) -> bool {
+    a.trim_start_matches("W/") == b.trim_start_matches("W/")
+}
+
+// If-Range needs a strong match, or a date with the exact modification time,
+// which we don't support as dates aren't precise enough
+fn if_range_matches(headers: &Headers, etag: &str) -> bool {
+    match headers.get("If-Range") {
+        Some(tag) => tag.trim() == etag,
+        None => true,
+    }
+}
+
+// Parses a single bytes range. Returns None if it should be ignored, and an
+// error if it can't be satisfied for the given size
+fn byte_range(value: &str, total: usize) -> Result<Option<Range<usize>>, ()> {
+    let Some(range) = value.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+
+    let Some((start, end)) = range.trim().split_once("-") else {
+        return Ok(None);
+    };
+
+    // Multiple ranges would need a multipart response
+    if range.contains(",") {
+        return Ok(None);
+    }
+
+    let (start, end) = (start.trim(), end.trim());
+
+    let (start, end) = if start.is_empty() {
+        // Suffix ranges ask for the last bytes
+        let Ok(suffix) = end.parse::<usize>() else {
+            return Ok(None);
+        };
+
+        if suffix == 0 {
+            return Err(());
+        }
+
+        (total.saturating_sub(suffix), total)
+    } else {
+        let Ok(start) = start.parse::<usize>() else {
+            return Ok(None);
+        };
+
+        let end = match end {
+            "" => total,
+            end => match end.parse::<usize>() {
+                Ok(end) if end >= start => end.saturating_add(1).min(total),
+                _ => return Ok(None),
+            },
+        };
+
+        (start, end)
+    };
+
+    if start >= total {
+        return Err(());
+    }
+
+    Ok(Some(start..end))
+}
@@ -0,0 +1,53 @@
+use std::env;
+use std::sync::Arc;
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::TlsAcceptor;
+
+use crate::errors::{log_error, log_info};
+
+type TlsResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// The listener uses TLS only when both the certificate chain and its private
+// key are configured. As the server can't start as requested otherwise, any
+// error reading them is unrecoverable
+pub fn acceptor_from_env() -> Option<TlsAcceptor> {
+    let (Ok(cert_path), Ok(key_path)) = (env::var("TLS_CERT_PATH"), env::var("TLS_KEY_PATH")) else {
+        return None;
+    };
+
+    match acceptor(&cert_path, &key_path) {
+        Ok(acceptor) => {
+            log_info(format!("TLS enabled with certificate {}", cert_path));
+            Some(acceptor)
+        },
+        Err(e) => {
+            log_error(format!("Unable to load TLS certificate or key: {}", e).into());
+            panic!("Unrecoverable error! Check logs.");
+        },
+    }
+}
+
+// Builds the acceptor from PEM files, the first certificate being the
+// server's one followed by any intermediate ones
+pub fn acceptor(cert_path: &str, key_path: &str) -> TlsResult<TlsAcceptor> {
+    let certs = load_certs(cert_path)?;
+    let key = PrivateKeyDer::from_pem_file(key_path)?;
+
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+pub fn load_certs(path: &str) -> TlsResult<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)?.collect::<Result<Vec<_>, _>>()?;
+
+    if certs.is_empty() {
+        return Err(format!("No certificates found in {}", path).into());
+    }
+
+    Ok(certs)
+}
@@ -0,0 +1,35 @@
+use std::net::SocketAddr;
+use std::sync::LazyLock;
+
+use serde::Serialize;
+use tokio::sync::broadcast;
+
+// Subscribers that fall this far behind skip the events they missed
+const CAPACITY: usize = 64;
+
+static EVENTS: LazyLock<broadcast::Sender<WorkerEvent>> = LazyLock::new(|| broadcast::channel(CAPACITY).0);
+
+// Changes in the list of slaves, pushed to /workers/stream
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum WorkerEvent {
+    Registered { socket: SocketAddr, busy: usize },
+    Removed { socket: SocketAddr },
+    Busy { socket: SocketAddr, busy: usize },
+}
+
+impl WorkerEvent {
+    // Events only use the data field, so clients get them all as messages
+    pub fn to_frame(&self) -> Vec<u8> {
+        format!("data: {}\n\n", serde_json::to_string(self).unwrap()).into_bytes()
+    }
+}
+
+// Nobody may be listening, which is fine
+pub fn publish(event: WorkerEvent) {
+    let _ = EVENTS.send(event);
+}
+
+pub fn subscribe() -> broadcast::Receiver<WorkerEvent> {
+    EVENTS.subscribe()
+}
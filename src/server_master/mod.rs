@@ -1,11 +1,21 @@
+mod events;
+mod retry;
 pub mod routes;
 mod slaves;
 
 #[cfg(test)]
 mod tests {
     use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, Instant};
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+    use tokio_util::sync::CancellationToken;
+    use crate::client::pool::{Pool, PoolConfig};
     use crate::models::request;
     use crate::models::response::Response;
+    use crate::errors::slaves::*;
     use super::*;
 
     fn process_response(res: Response, equality: bool, to_test: u16) {
@@ -17,9 +27,12 @@ mod tests {
                     assert_ne!(res.status, to_test);
                 }
             },
-            Response::Buffer(res) => {
-                //convert to http as call itself
-                println!("{:#?}", res);
+            Response::Stream(res, _) => {
+                if equality {
+                    assert_eq!(res.status, to_test);
+                } else {
+                    assert_ne!(res.status, to_test);
+                }
             },
         }
     }
@@ -375,4 +388,270 @@ mod tests {
 
         process_response(res, false, 400);
     }
-}
\ No newline at end of file
+
+    fn upload_request(content_type: &str, body: request::Body) -> request::HttpRequest {
+        let mut req = request::HttpRequest {
+            method: "POST".to_string(),
+            uri: vec!["files".to_string()],
+            version: "1.1".to_string(),
+            body,
+            ..Default::default()
+        };
+        req.headers.insert("Content-Type".to_string(), content_type.to_string());
+        req
+    }
+
+    #[tokio::test]
+    async fn upload_name_error() {
+        let remote = "0.0.0.0:0".to_string().parse::<SocketAddr>().unwrap();
+
+        for name in ["../secret", ".hidden", "a b.txt", ""] {
+            let mut req = upload_request("text/plain", request::Body::Binary(b"hola".to_vec()));
+            req.params.insert("name".to_string(), name.to_string());
+            let res = routes::handle_route(req, remote).await;
+
+            process_response(res, true, 400);
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_missing_name_error() {
+        let remote = "0.0.0.0:0".to_string().parse::<SocketAddr>().unwrap();
+        let req = upload_request("text/plain", request::Body::Binary(b"hola".to_vec()));
+        let res = routes::handle_route(req, remote).await;
+
+        process_response(res, true, 400);
+    }
+
+    #[tokio::test]
+    async fn upload_multipart_error() {
+        let remote = "0.0.0.0:0".to_string().parse::<SocketAddr>().unwrap();
+
+        // The only field isn't a file
+        let body = b"--b\r\nContent-Disposition: form-data; name=\"text\"\r\n\r\nhola\r\n--b--\r\n".to_vec();
+        let req = upload_request("multipart/form-data; boundary=b", request::Body::Binary(body));
+        let res = routes::handle_route(req, remote).await;
+        process_response(res, true, 400);
+
+        let req = upload_request("multipart/form-data; boundary=b", request::Body::Binary(b"--b\r\n".to_vec()));
+        let res = routes::handle_route(req, remote).await;
+        process_response(res, true, 400);
+    }
+
+    #[tokio::test]
+    async fn upload_urlencoded_error() {
+        let remote = "0.0.0.0:0".to_string().parse::<SocketAddr>().unwrap();
+        let body = request::Body::URLdec(vec![("content".to_string(), "hola".to_string())]);
+        let mut req = upload_request("application/x-www-form-urlencoded", body);
+        req.params.insert("name".to_string(), "a.txt".to_string());
+        let res = routes::handle_route(req, remote).await;
+
+        process_response(res, true, 415);
+    }
+
+    #[tokio::test]
+    async fn workers_stream_success() {
+        let remote = "0.0.0.0:0".to_string().parse::<SocketAddr>().unwrap();
+        let req = request::HttpRequest {
+            method: "GET".to_string(),
+            uri: vec!["workers".to_string(), "stream".to_string()],
+            version: "1.1".to_string(),
+            ..Default::default()
+        };
+
+        let Response::Stream(res, mut receiver) = routes::handle_route(req, remote).await else {
+            panic!("Expected a stream");
+        };
+
+        assert_eq!(res.status, 200);
+        assert_eq!(res.headers.get("Content-Type").unwrap(), "text/event-stream");
+
+        // Events published after subscribing are pushed as they happen
+        let socket = "10.0.0.7:7878".parse::<SocketAddr>().unwrap();
+        events::publish(events::WorkerEvent::Busy { socket, busy: 2 });
+
+        let expected = b"data: {\"event\":\"busy\",\"socket\":\"10.0.0.7:7878\",\"busy\":2}\n\n".to_vec();
+
+        loop {
+            let frame = tokio::time::timeout(std::time::Duration::from_secs(1), receiver.recv()).await.unwrap().unwrap();
+
+            if frame == expected {
+                break;
+            }
+        }
+    }
+
+    fn quick_policy() -> retry::RetryPolicy {
+        retry::RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_success() {
+        let attempts = AtomicUsize::new(0);
+
+        // Fails twice before the slave answers
+        let res = quick_policy().run(true, || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Err::<u16, Box<dyn std::error::Error + Send + Sync>>(Box::new(SlaveFailedError)),
+                _ => Ok(200),
+            }
+        }).await;
+
+        assert_eq!(res.unwrap(), 200);
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_exhausted_error() {
+        let attempts = AtomicUsize::new(0);
+
+        let res = quick_policy().run(true, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), Box<dyn std::error::Error + Send + Sync>>(Box::new(SlaveFailedError))
+        }).await;
+
+        assert!(res.unwrap_err().is::<RetriesExhaustedError>());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+
+        // Other errors are returned right away
+        let res = quick_policy().run(true, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), Box<dyn std::error::Error + Send + Sync>>(Box::new(SlavesMissingError))
+        }).await;
+
+        assert!(res.unwrap_err().is::<SlavesMissingError>());
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn retry_deadline_error() {
+        let policy = retry::RetryPolicy {
+            max_attempts: 100,
+            attempt_timeout: Duration::from_millis(20),
+            deadline: Duration::from_millis(200),
+            ..quick_policy()
+        };
+
+        // Slaves that never answer only hold the request until the deadline
+        let start = Instant::now();
+        let res = policy.run(true, || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }).await;
+
+        assert!(res.unwrap_err().is::<RetriesExhaustedError>());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn retry_timeout_error() {
+        let attempts = AtomicUsize::new(0);
+        let policy = retry::RetryPolicy {
+            attempt_timeout: Duration::from_millis(20),
+            ..quick_policy()
+        };
+
+        // The slow slave may still do the request, so it isn't sent again
+        let res = policy.run(false, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(())
+        }).await;
+
+        assert!(res.unwrap_err().is::<SlaveTimeoutError>());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        // Failed slaves are still retried
+        let res = policy.run(false, || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), Box<dyn std::error::Error + Send + Sync>>(Box::new(SlaveFailedError))
+        }).await;
+
+        assert!(res.unwrap_err().is::<RetriesExhaustedError>());
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn retry_backoff_success() {
+        let policy = retry::RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            ..Default::default()
+        };
+
+        for _ in 0..100 {
+            assert!(policy.backoff(0) <= Duration::from_millis(100));
+            assert!(policy.backoff(2) <= Duration::from_millis(400));
+            assert!(policy.backoff(20) <= Duration::from_millis(1000));
+        }
+    }
+
+    // Reads each request and closes the connection without answering,
+    // counting the requests read
+    async fn start_dropping_slave() -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let requests_clone = Arc::clone(&requests);
+
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0u8; 1024];
+
+                while !request.ends_with(b"\r\n\r\n") {
+                    let read = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..read]);
+                }
+
+                requests_clone.fetch_add(1, Ordering::SeqCst);
+            }
+        });
+
+        (address, requests)
+    }
+
+    #[tokio::test]
+    async fn retry_response_lost_error() {
+        let (address, requests) = start_dropping_slave().await;
+        let pool = Pool::new(PoolConfig::default());
+        let token = CancellationToken::new();
+
+        let mut req = request::HttpRequest {
+            method: "POST".to_string(),
+            uri: vec!["createfile".to_string()],
+            version: "HTTP/1.1".to_string(),
+            ..Default::default()
+        };
+
+        // The slave may have created the file, so it isn't asked again
+        let res = quick_policy().run(false, || async {
+            pool.send(address, &token, req.clone()).await.map_err(retry::slave_error)
+        }).await;
+
+        assert!(res.unwrap_err().is::<SlaveResponseLostError>());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Requests that can be repeated are sent to the slaves again
+        req.method = "GET".to_string();
+        let res = quick_policy().run(true, || async {
+            pool.send(address, &token, req.clone()).await.map_err(retry::slave_error)
+        }).await;
+
+        assert!(res.unwrap_err().is::<RetriesExhaustedError>());
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        // Slaves that can't be reached never got the request
+        let res = quick_policy().run(false, || async {
+            pool.send("127.0.0.1:1".parse().unwrap(), &token, req.clone()).await.map_err(retry::slave_error)
+        }).await;
+
+        assert!(res.unwrap_err().is::<RetriesExhaustedError>());
+    }
+}
@@ -0,0 +1,120 @@
+use std::future::Future;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use tokio::time::{sleep, timeout, Instant};
+
+use crate::errors::client::RequestNotSentError;
+use crate::errors::slaves::{RetriesExhaustedError, SlaveFailedError, SlaveResponseLostError, SlaveTimeoutError};
+use crate::server_base::limits::{read_secs, read_var};
+
+type RetryResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+static POLICY: LazyLock<RetryPolicy> = LazyLock::new(RetryPolicy::from_env);
+
+// How requests are sent again when their slave fails. Each value can be
+// overridden with an env var
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // Attempts per request, counting the first one
+    pub max_attempts: usize,
+    // Wait before the first retry, doubled for each one after it
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // Time a slave gets to answer a single attempt
+    pub attempt_timeout: Duration,
+    // Time all the attempts get together
+    pub deadline: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            attempt_timeout: Duration::from_secs(60),
+            deadline: Duration::from_secs(180),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> RetryPolicy {
+        let default = RetryPolicy::default();
+
+        RetryPolicy {
+            max_attempts: read_var("RETRY_MAX_ATTEMPTS").filter(|max| *max > 0).unwrap_or(default.max_attempts),
+            base_delay: read_var("RETRY_BASE_DELAY_MS").map(|ms| Duration::from_millis(ms as u64)).unwrap_or(default.base_delay),
+            max_delay: read_var("RETRY_MAX_DELAY_MS").map(|ms| Duration::from_millis(ms as u64)).unwrap_or(default.max_delay),
+            attempt_timeout: read_secs("RETRY_ATTEMPT_TIMEOUT").unwrap_or(default.attempt_timeout),
+            deadline: read_secs("RETRY_DEADLINE").unwrap_or(default.deadline),
+        }
+    }
+
+    // A random wait up to the exponential delay, so the retries of the many
+    // parts of a task don't reach the slaves all at once
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let ceiling = self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry.try_into().unwrap_or(u32::MAX)))
+            .min(self.max_delay);
+
+        ceiling.mul_f64(rand::random::<f64>())
+    }
+
+    // Runs the attempt until it succeeds or fails with an error other than a
+    // failed slave, and retries it until the policy runs out. A slave that got
+    // the request may still do it, so attempts that time out or lose their
+    // response are only retried if the request can be repeated
+    pub async fn run<T, F, Fut>(&self, repeatable: bool, mut attempt: F) -> RetryResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = RetryResult<T>>,
+    {
+        let deadline = Instant::now() + self.deadline;
+
+        for retry in 0..self.max_attempts {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            match timeout(self.attempt_timeout.min(remaining), attempt()).await {
+                Ok(Ok(value)) => return Ok(value),
+                Ok(Err(e)) if e.is::<SlaveResponseLostError>() && !repeatable => return Err(e),
+                Ok(Err(e)) if !e.is::<SlaveFailedError>() && !e.is::<SlaveResponseLostError>() => return Err(e),
+                Ok(Err(_)) => {},
+                Err(_) if !repeatable => return Err(Box::new(SlaveTimeoutError)),
+                Err(_) => {},
+            }
+
+            let wait = self.backoff(retry);
+
+            // The last attempt doesn't need a wait, nor one that would end
+            // past the deadline
+            if retry + 1 == self.max_attempts || Instant::now() + wait >= deadline {
+                break;
+            }
+
+            sleep(wait).await;
+        }
+
+        Err(Box::new(RetriesExhaustedError))
+    }
+}
+
+pub fn policy() -> &'static RetryPolicy {
+    &POLICY
+}
+
+// Failures before the request reached the slave can always be retried, the
+// ones after it only if the request can be repeated
+pub fn slave_error(e: Box<dyn std::error::Error + Send + Sync>) -> Box<dyn std::error::Error + Send + Sync> {
+    if e.is::<RequestNotSentError>() || e.is::<SlaveFailedError>() {
+        Box::new(SlaveFailedError)
+    } else {
+        Box::new(SlaveResponseLostError)
+    }
+}
+
+// Requests that don't change anything in the slaves
+pub fn is_repeatable(method: &str) -> bool {
+    method == "GET" || method == "HEAD"
+}
@@ -1,11 +1,13 @@
 use tokio::select;
+use tokio::sync::{broadcast, mpsc, Semaphore};
 use tokio::task::JoinSet;
 
-use std::collections::HashMap;
 use std::env;
 use std::net::SocketAddr;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
 
-use crate::client::client;
+use crate::client::pool;
 use crate::errors::log_error;
 use crate::models::matrix;
 use crate::models::slave::Slave;
@@ -14,104 +16,115 @@ use crate::{errors, functions};
 use crate::models::request::{Body, HttpRequest};
 use crate::models::response::{HttpResponse, Response};
 use crate::redis_comm;
-
-use super::slaves;
-
-pub async  fn handle_route(req: HttpRequest, remote: SocketAddr) -> Response {
-    // Based on parsing logic, the vector will always have at least 1 item
-    let base_uri = req.uri[0].as_str();
-
-    match base_uri {
-        "createfile" => send_request_atomic(req).await,
-        "deletefile" => send_request_atomic(req).await,
-        "fibonacci" => send_request_atomic(req).await,
-        "hash" => send_request_atomic(req).await,
-        "help" => help(req),
-        "loadtest" => loadtest(req).await,
-        "random" => send_request_atomic(req).await,
-        "reverse" => send_request_atomic(req).await,
-        "simulate" => send_request_atomic(req).await,
-        "sleep" => send_request_atomic(req).await,
-        "timestamp" => send_request_atomic(req).await,
-        "toupper" => send_request_atomic(req).await,
-        "countwords" => count_words(req).await,
-        "matrixmult" => matrix_multiplication(req).await,
-        "workers" => workers(req).await,
-        "slave" => add_slave(req, remote).await,
-        _ => Response::HTTP(HttpResponse::basic(404))
-    }
+use crate::server_base::limits::Limits;
+use crate::server_base::{multipart, request_id, shutdown, static_files};
+use crate::status::status;
+use crate::server_base::router::{BoxFuture, Context, Route, Router};
+
+use super::events::{self, WorkerEvent};
+use super::{retry, slaves};
+
+// Proxies usually close connections idle for a minute, so /workers/stream
+// sends a comment more often than that
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+static ROUTER: LazyLock<Router<Response>> = LazyLock::new(router);
+
+// Routes run by a single slave are forwarded as they are, their parameters
+// are declared here to reject invalid requests early and to list them in /help
+fn router() -> Router<Response> {
+    Router::new()
+        .route(Route::asynchronous("GET", "/fibonacci", forward).param::<u128>("num"))
+        .route(Route::asynchronous("POST", "/createfile", forward).param::<String>("name").param::<String>("content").param::<u64>("repeat"))
+        .route(Route::asynchronous("DELETE", "/deletefile", forward).param::<String>("name"))
+        .route(Route::asynchronous("GET", "/reverse", forward).param::<String>("text"))
+        .route(Route::asynchronous("GET", "/toupper", forward).param::<String>("text"))
+        .route(Route::asynchronous("GET", "/random", forward).param::<usize>("count").param::<i32>("min").param::<i32>("max"))
+        .route(Route::asynchronous("GET", "/timestamp", forward))
+        .route(Route::asynchronous("GET", "/hash", forward).param::<String>("text"))
+        .route(Route::asynchronous("GET", "/simulate", forward).param::<u64>("seconds").param::<String>("task"))
+        .route(Route::asynchronous("GET", "/sleep", forward).param::<u64>("seconds"))
+        .route(Route::asynchronous("GET", "/loadtest", |req, ctx| Box::pin(loadtest(req, ctx))).param::<u64>("tasks").param::<u64>("sleep"))
+        .route(Route::asynchronous("GET", "/matrixmult", |req, _| Box::pin(matrix_multiplication(req))))
+        .route(Route::asynchronous("GET", "/countwords", |req, ctx| Box::pin(count_words(req, ctx))).param::<String>("name"))
+        .route(Route::asynchronous("GET", "/workers", |req, _| Box::pin(workers(req))))
+        .route(Route::sync("GET", "/workers/stream", workers_stream))
+        .route(Route::asynchronous("GET", "/workers/{index}", |req, ctx| Box::pin(worker(req, ctx))))
+        .route(Route::sync("POST", "/files", upload))
+        .route(Route::sync("GET", "/files/{name}", download))
+        .route(Route::sync("GET", "/status", status))
+        .route(Route::sync("GET", "/help", help))
+        .route(Route::asynchronous("POST", "/slave", |req, ctx| Box::pin(add_slave(req, ctx))).param::<u16>("port").param::<String>("slave_code").hidden())
 }
 
-fn help(req: HttpRequest) -> Response {
-    if req.method != "GET" {
-        return Response::HTTP(HttpResponse::basic(405));
-    }
-
-    let run = functions::help::help();
-    Response::HTTP(valid_request(run))
+pub async fn handle_route(req: HttpRequest, remote: SocketAddr) -> Response {
+    ROUTER.handle(req, remote).await.unwrap_or_else(Response::HTTP)
 }
 
-async fn loadtest(req: HttpRequest) -> Response {
-    if req.method != "GET" {
-        return Response::HTTP(HttpResponse::basic(405));
-    }
-
-    let Some(tasks) = req.params.get("tasks") else {
-        return Response::HTTP(invalid_request("Missing parameter: tasks".to_string()));
-    };
+fn forward(req: HttpRequest, _: Context) -> BoxFuture<Response> {
+    Box::pin(send_request_atomic(req))
+}
 
-    let Some(sleep) = req.params.get("sleep") else {
-        return Response::HTTP(invalid_request("Missing parameter: sleep".to_string()));
-    };
+// Load of the master itself, the one of each slave is in /workers
+fn status(_: HttpRequest, _: Context) -> Response {
+    Response::HTTP(HttpResponse::json(200, status::status()))
+}
 
-    let Ok(tasks) = tasks.parse::<u64>() else {
-        return Response::HTTP(invalid_request("Unable to parse tasks!".to_string()));
-    };
+fn help(_: HttpRequest, _: Context) -> Response {
+    Response::HTTP(valid_request(ROUTER.help()))
+}
 
-    let Ok(sleep) = sleep.parse::<u64>() else {
-        return Response::HTTP(invalid_request("Unable to parse sleep!".to_string()));
-    };
+async fn loadtest(_: HttpRequest, ctx: Context) -> Response {
+    let tasks = *ctx.get::<u64>("tasks");
+    let sleep = *ctx.get::<u64>("sleep");
 
     let mut task_handles= JoinSet::new();
 
+    // Only as many requests as the master admits are sent at once, so big
+    // tests don't hold a task for each one
+    let permits = Arc::new(Semaphore::new(Limits::from_env("MASTER").max_in_flight));
+
     for _ in 0..tasks {
+        let Ok(permit) = Arc::clone(&permits).acquire_owned().await else {
+            break;
+        };
+
         let mut req = HttpRequest::default();
         req.method = "GET".to_string();
         req.uri.push("sleep".to_string());
         req.params.insert("seconds".to_string(), sleep.to_string());
         req.version = "HTTP/1.1".to_string();
         
-        task_handles.spawn(async move {
+        task_handles.spawn(request_id::propagate(async move {
+            let _permit = permit;
             send_request_partial(req).await
-        });
-    }
+        }));
 
-    while let Some(res) = task_handles.join_next().await {
-        if let Ok(res) = res {
-            // The only error possible is being out of slaves
-            if let Err(_) = res {
+        // Finished requests are checked as we go, so we stop sending early
+        // if we run out of slaves
+        while let Some(res) = task_handles.try_join_next() {
+            if let Ok(Err(e)) = res {
                 task_handles.abort_all();
-                return Response::HTTP(missing_slaves());
+                return Response::HTTP(failed_request(&*e));
             }
         }
     }
 
+    while let Some(res) = task_handles.join_next().await {
+        if let Ok(Err(e)) = res {
+            task_handles.abort_all();
+            return Response::HTTP(failed_request(&*e));
+        }
+    }
+
     let contents = format!("{tasks} sleep tasks with a duration of {sleep} seconds were spawned");
     Response::HTTP(valid_request(contents))
 }
 
-async fn count_words(req: HttpRequest) -> Response {
+async fn count_words(req: HttpRequest, ctx: Context) -> Response {
     // We handle scenarios in the master to simplify slaves' execution of this
     // parallelized job
-    if req.method != "GET" {
-        return Response::HTTP(HttpResponse::basic(405));
-    }
-
-    let Some(name) = req.params.get("name") else {
-        return Response::HTTP(invalid_request("Missing parameter: name".to_string()));
-    };
-
-    let filepath = format!("archivos/{}", name);
+    let name = ctx.get::<String>("name");
 
     // We check if we have counted the given file before. If the request fails
     // for anything other than a connection refused, we can continue
@@ -126,7 +139,8 @@ async fn count_words(req: HttpRequest) -> Response {
         },
     }
 
-    match std::fs::exists(filepath) {
+    // Uploaded files can be on any node, so we check Redis too
+    match functions::files::exists(name) {
         Ok(res) => {
             if !res {
                 return Response::HTTP(invalid_request("Could not read file".to_string()));
@@ -155,9 +169,9 @@ async fn count_words(req: HttpRequest) -> Response {
         partial.version = req.version.clone();
         partial.headers = req.headers.clone();
 
-        partial_task_handles.spawn(async move {            
+        partial_task_handles.spawn(request_id::propagate(async move {
             send_request_partial(partial).await
-        });
+        }));
     }
     
     while let Some(res) = partial_task_handles.join_next().await {
@@ -165,12 +179,7 @@ async fn count_words(req: HttpRequest) -> Response {
             if let Err(e) = res {
                 log_error(e.to_string().into());
                 partial_task_handles.abort_all();
-
-                if e.is::<errors::slaves::SlavesMissingError>() {
-                    return Response::HTTP(missing_slaves());
-                } else {
-                    return Response::HTTP(server_issue_response());
-                }
+                return Response::HTTP(failed_request(&*e));
             }
         }
     }
@@ -182,6 +191,8 @@ async fn count_words(req: HttpRequest) -> Response {
     aggregate.params.insert("name".to_string(), name.to_string());
     aggregate.version = req.version.clone();
     aggregate.headers = req.headers.clone();
+    // The aggregate gets its own sub-ID, like the partial tasks
+    aggregate.headers.remove(request_id::HEADER);
 
     send_request_atomic(aggregate).await
 }
@@ -189,10 +200,6 @@ async fn count_words(req: HttpRequest) -> Response {
 async fn matrix_multiplication(req: HttpRequest) -> Response {
     // We handle scenarios in the master to simplify slaves' execution of this
     // parallelized job
-    if req.method != "GET" {
-        return Response::HTTP(HttpResponse::basic(405));
-    }
-
     let body = match req.body {
         Body::JSON(content) => content,
         _ => return Response::HTTP(invalid_request("Missing JSON content with matrices!".to_string())),
@@ -215,15 +222,7 @@ async fn matrix_multiplication(req: HttpRequest) -> Response {
     match redis_comm::matrix_store::get_matrix_res(&job) {
         Ok(res) => {
             let res = serde_json::to_string(&res).unwrap();
-            let version = "HTTP/1.1".to_string();
-            let status = 200;
-            let mut headers = HashMap::<String, String>::new();
-            headers.insert("Content-Type".to_string(), "application/json".to_string());
-            headers.insert("Content-Length".to_string(), res.len().to_string());
-
-            let res = HttpResponse::new(version, status, headers, res);
-
-            return Response::HTTP(res);
+            return Response::HTTP(HttpResponse::json(200, res));
         },
         Err(e) => {
             if e.is_connection_refusal() {
@@ -254,9 +253,9 @@ async fn matrix_multiplication(req: HttpRequest) -> Response {
             partial.params.insert("column".to_string(), j.to_string());
             partial.version = "HTTP/1.1".to_string();
 
-            partial_task_handles.spawn(async move {            
+            partial_task_handles.spawn(request_id::propagate(async move {
                 send_request_partial(partial).await
-            });
+            }));
         }
     }
 
@@ -265,12 +264,7 @@ async fn matrix_multiplication(req: HttpRequest) -> Response {
             if let Err(e) = res {
                 log_error(e.to_string().into());
                 partial_task_handles.abort_all();
-
-                if e.is::<errors::slaves::SlavesMissingError>() {
-                    return Response::HTTP(missing_slaves());
-                } else {
-                    return Response::HTTP(server_issue_response());
-                }
+                return Response::HTTP(failed_request(&*e));
             }
         }
     }
@@ -288,16 +282,14 @@ async fn matrix_multiplication(req: HttpRequest) -> Response {
 async fn workers(req: HttpRequest) -> Response {
     // We handle scenarios in the master to simplify slaves' execution of this
     // parallelized job
-    if req.method != "GET" {
-        return Response::HTTP(HttpResponse::basic(405));
-    }
-
     let slaves = slaves::get_quantity();
 
-    let mut worker_status: Vec<Status> = vec![];
+    if slaves == 0 {
+        return Response::HTTP(missing_slaves());
+    }
 
     // This set allocates all partial tasks handles so we can check for errors
-    let mut partial_task_handles = JoinSet::<Option<Response>>::new();
+    let mut partial_task_handles = JoinSet::<Option<HttpResponse>>::new();
 
     for index in 0..slaves {
         let mut req = req.clone();
@@ -315,177 +307,300 @@ async fn workers(req: HttpRequest) -> Response {
         });
     }
 
+    let mut worker_status: Vec<Status> = vec![];
+
     while let Some(res) = partial_task_handles.join_next().await {
         // We can ignore the error because it means the slave is gone
-        if let Ok(res) = res {
-            if let Some(res) = res {
-                // The function only returns buffers, so we can ignore the http arm
-                if let Response::Buffer(buf) = res {
-                    let res = HttpResponse::from(buf);
-                    let status = serde_json::from_str::<Status>(&res.contents).unwrap();
-                    worker_status.push(status);
-                }
-            }
+        let Ok(Some(res)) = res else {
+            continue;
+        };
+
+        // We only answer with statuses that were valid
+        if let Ok(status) = serde_json::from_slice::<Status>(&res.contents) {
+            worker_status.push(status);
         }
     }
 
-    if worker_status.len() == 0 {
+    // Slaves that are registered but don't answer are as good as missing
+    if worker_status.is_empty() {
         return Response::HTTP(missing_slaves());
     }
 
-    let version = "HTTP/1.1".to_string();
-    let status = 200;
-    let headers = HashMap::new();
-    let contents = serde_json::to_string(&worker_status).unwrap();
+    Response::HTTP(HttpResponse::json(200, serde_json::to_string(&worker_status).unwrap()))
+}
+
+// Stores a file sent as a multipart/form-data field or as the raw body. The
+// name param is required for raw bodies, and overrides the multipart filename
+fn upload(req: HttpRequest, _: Context) -> Response {
+    let (name, content) = match upload_contents(req) {
+        Ok(upload) => upload,
+        Err(res) => return Response::HTTP(res),
+    };
+
+    let size = content.len();
+
+    match functions::files::store(&name, &content) {
+        Ok(_) => {
+            let mut res = HttpResponse::text(201, format!("file={},size={}", name, size));
+            res.headers.insert("Location".to_string(), format!("/files/{}", name));
+            Response::HTTP(res)
+        },
+        Err(e) if e.is::<errors::files::InvalidFileNameError>() => Response::HTTP(invalid_request(e.to_string())),
+        Err(e) if e.is::<errors::files::FileExistsError>() => Response::HTTP(HttpResponse::text(409, e.to_string())),
+        Err(e) if e.is::<errors::files::FileTooLargeError>() => Response::HTTP(HttpResponse::text(413, e.to_string())),
+        Err(e) => {
+            log_error(e);
+            Response::HTTP(server_issue_response())
+        },
+    }
+}
+
+fn upload_contents(req: HttpRequest) -> Result<(String, Vec<u8>), HttpResponse> {
+    let name = req.param("name").cloned();
+    let content_type = req.headers.get("Content-Type").cloned().unwrap_or_default();
+
+    let content = match req.body {
+        Body::Binary(content) if multipart::is_multipart(&content_type) => {
+            let Ok(parts) = multipart::parse(&content_type, &content) else {
+                return Err(invalid_request("Invalid multipart body!".to_string()));
+            };
+
+            // The file field is preferred, though any with a filename works
+            let mut files = parts.into_iter().filter(|part| part.filename.is_some());
+            let Some(part) = files.clone().find(|part| part.name == "file").or_else(|| files.next()) else {
+                return Err(invalid_request("Missing file field!".to_string()));
+            };
+
+            // The filename is only used if no name was given
+            let filename = part.filename.unwrap_or_default();
+            return Ok((name.unwrap_or(filename), part.content));
+        },
+        Body::Binary(content) => content,
+        Body::JSON(content) => content.into_bytes(),
+        Body::URLdec(_) => return Err(HttpResponse::text(415, "Use multipart/form-data or a raw body to upload files!")),
+    };
+
+    match name {
+        Some(name) => Ok((name, content)),
+        None => Err(invalid_request("Missing parameter: name".to_string())),
+    }
+}
+
+// Sends a file from this node's directory or, if it was uploaded to another
+// node, from Redis. Conditional and range requests are handled here too
+fn download(req: HttpRequest, ctx: Context) -> Response {
+    let name = ctx.capture("name");
+
+    let content = match functions::files::read(name) {
+        Ok(content) => content,
+        Err(e) if e.is::<errors::files::InvalidFileNameError>() => return Response::HTTP(HttpResponse::basic(404)),
+        Err(e) if e.downcast_ref::<std::io::Error>().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) => {
+            return Response::HTTP(HttpResponse::basic(404));
+        },
+        Err(e) => {
+            log_error(e);
+            return Response::HTTP(server_issue_response());
+        },
+    };
+
+    let modified = functions::files::modified(name);
+    Response::HTTP(static_files::serve(&req, content, modified, static_files::content_type(name)))
+}
+
+// Pushes an event whenever a slave registers, is removed or reports a
+// different amount of busy threads. The current slaves are sent first, so
+// clients don't need to ask /workers
+fn workers_stream(_: HttpRequest, _: Context) -> Response {
+    let mut events = events::subscribe();
+    let (sender, receiver) = mpsc::channel::<Vec<u8>>(16);
+
+    let mut res = HttpResponse::binary(200, vec![], "text/event-stream");
+    res.headers.insert("Cache-Control".to_string(), "no-cache".to_string());
+
+    tokio::spawn(async move {
+        for slave in slaves::get_all() {
+            let busy = *slave.busy.lock();
+            let event = WorkerEvent::Registered { socket: slave.socket, busy };
 
-    Response::HTTP(HttpResponse::new(version, status, headers, contents))
+            if sender.send(event.to_frame()).await.is_err() {
+                return;
+            }
+        }
+
+        let token = shutdown::token();
+        let mut heartbeat = tokio::time::interval_at(tokio::time::Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+
+        loop {
+            let frame = tokio::select! {
+                // The stream ends when shutting down or once the client is gone
+                _ = token.cancelled() => return,
+                _ = sender.closed() => return,
+                _ = heartbeat.tick() => b": heartbeat\n\n".to_vec(),
+                event = events.recv() => match event {
+                    Ok(event) => event.to_frame(),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return,
+                },
+            };
+
+            if sender.send(frame).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    Response::Stream(res, receiver)
+}
+
+// Status of a single slave, by its position in the list of slaves
+async fn worker(mut req: HttpRequest, ctx: Context) -> Response {
+    let Some(slave) = ctx.capture("index").parse::<usize>().ok().and_then(slaves::get_specific) else {
+        return Response::HTTP(HttpResponse::basic(404));
+    };
+
+    req.uri = vec!["status".to_string()];
+
+    match send_request_specific(req, slave).await {
+        Ok(res) => Response::HTTP(res),
+        Err(e) => Response::HTTP(failed_request(&*e)),
+    }
 }
 
 fn invalid_request(contents: String) -> HttpResponse {
-    let version = "HTTP/1.1".to_string();
-    let status = 400;
-    let headers = HashMap::new();
-    
-    HttpResponse::new(version, status, headers, contents)
+    HttpResponse::text(400, contents)
 }
 
 fn missing_slaves() -> HttpResponse {
-    let version = "HTTP/1.1".to_string();
-    let status = 500;
-    let headers = HashMap::new();
-    let contents = "Unable to process your request at this time.\nTry again later.".to_string();
-    HttpResponse::new(version, status, headers, contents)
+    HttpResponse::text(500, "Unable to process your request at this time.\nTry again later.")
 }
 
 fn valid_request(contents: String) -> HttpResponse {
-    let version = "HTTP/1.1".to_string();
-    let status = 200;
-    let headers = HashMap::new();
-    
-    HttpResponse::new(version, status, headers, contents)
+    HttpResponse::text(200, contents)
 }
 
 fn server_issue_response() -> HttpResponse {
-    let contents = "Unable to process your request at this time!".to_string();
-    HttpResponse::new("HTTP/1.1".to_string(), 500, HashMap::new(), contents)
+    HttpResponse::text(500, "Unable to process your request at this time!")
 }
 
-async fn send_request_atomic(req: HttpRequest) -> Response {
-    loop {
-        match send_request_base(req.clone()).await {
-            Ok(res) => return res,
-            Err(e) => {
-                if e.is::<errors::slaves::SlavesMissingError>() {
-                    return Response::HTTP(missing_slaves());
-                } else {
-                    continue;
-                }
-            },
-        }
+fn bad_gateway() -> HttpResponse {
+    HttpResponse::text(502, "The slave failed while processing your request.\nCheck its result before trying again.")
+}
+
+fn gateway_timeout() -> HttpResponse {
+    HttpResponse::text(504, "The slaves took too long to process your request.\nTry again later.")
+}
+
+// Answer to the client when a request, or one of the parts of a task, couldn't
+// be done by the slaves
+fn failed_request(e: &(dyn std::error::Error + Send + Sync + 'static)) -> HttpResponse {
+    if e.is::<errors::slaves::SlavesMissingError>() {
+        missing_slaves()
+    } else if e.is::<errors::slaves::RetriesExhaustedError>() || e.is::<errors::slaves::SlaveTimeoutError>() {
+        gateway_timeout()
+    } else if e.is::<errors::slaves::SlaveResponseLostError>() {
+        bad_gateway()
+    } else {
+        server_issue_response()
     }
 }
 
-// We error out if we are out of slaves is important or if redis is down, as it
-// is required for parallelized tasks
-async fn send_request_partial(req: HttpRequest) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    loop {
-        match send_request_base(req.clone()).await {
-            Ok(res) => {
-                // The function on returns this Response kind
-                if let Response::Buffer(buf) = res {
-                    let res = HttpResponse::from(buf);
-
-                    // Currently, the only scenario is having redis down
-                    if res.status != 200 {
-                        return Err(res.contents.into());
-                    } else {
-                        return Ok(());
-                    }
-                }
-            },
-            Err(e) => {
-                if e.is::<errors::slaves::SlavesMissingError>() {
-                    return Err(e);
-                } else {
-                    continue;
-                }
-            },
-        }
+async fn send_request_atomic(mut req: HttpRequest) -> Response {
+    request_id::tag_forwarded(&mut req);
+
+    let repeatable = retry::is_repeatable(&req.method);
+
+    match retry::policy().run(repeatable, || send_request_base(req.clone())).await {
+        Ok(res) => Response::HTTP(res),
+        Err(e) => Response::HTTP(failed_request(&*e)),
     }
 }
 
-async fn send_request_specific(req: HttpRequest, slave: Slave) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+// We error out if we are out of slaves, if they keep failing or if redis is
+// down, as it is required for parallelized tasks
+async fn send_request_partial(mut req: HttpRequest) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    request_id::tag_partial(&mut req);
+
+    let repeatable = retry::is_repeatable(&req.method);
+    let res = retry::policy().run(repeatable, || send_request_base(req.clone())).await?;
+
+    // Currently, the only scenario is having redis down
+    if res.status != 200 {
+        return Err(res.contents_str().into());
+    }
+
+    Ok(())
+}
+
+// Slaves' responses are answered to the client like the master's own ones, so
+// only the headers about the slave's connection are dropped
+fn relayed(mut res: HttpResponse) -> HttpResponse {
+    res.headers.remove("Connection");
+    res.headers.remove("Keep-Alive");
+    res.headers.remove("Transfer-Encoding");
+    res
+}
+
+// The request is meant for this slave, so it isn't retried on another one, but
+// it can't wait on it forever either
+async fn send_request_specific(req: HttpRequest, slave: Slave) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
+    // The pool fails right away if the slave was already removed, so it goes
+    // first. Otherwise, the slave may have gotten the request before failing
     select! {
-        buffer = client::send_async_request(slave.socket, req) => {
-            match buffer {
-                Ok(buffer) => {
-                    if buffer.len() == 0 {
-                        Err(Box::new(errors::slaves::SlaveFailedError))
-                    } else {
-                        Ok(Response::Buffer(buffer))
-                    }
-                },
-                Err(_) => {
-                    Err(Box::new(errors::slaves::SlaveFailedError))
-                }
+        biased;
+
+        res = pool::send(slave.socket, &slave.token, req) => {
+            match res {
+                Ok(res) => Ok(relayed(res)),
+                Err(e) => Err(retry::slave_error(e)),
             }
         }
 
         _ = slave.token.cancelled() => {
-            Err(Box::new(errors::slaves::SlaveFailedError))
+            Err(Box::new(errors::slaves::SlaveResponseLostError))
+        }
+
+        _ = tokio::time::sleep(retry::policy().attempt_timeout) => {
+            Err(Box::new(errors::slaves::SlaveTimeoutError))
         }
     }
 }
 
-async fn send_request_base(req: HttpRequest) -> Result<Response, Box<dyn std::error::Error + Send + Sync>> {
+async fn send_request_base(req: HttpRequest) -> Result<HttpResponse, Box<dyn std::error::Error + Send + Sync>> {
     let Some(slave) = slaves::get_current() else {
         return Err(Box::new(errors::slaves::SlavesMissingError));
     };
 
+    // The pool fails right away if the slave was already removed, so it goes
+    // first. Otherwise, the slave may have gotten the request before failing
     select! {
-        buffer = client::send_async_request(slave.socket, req) => {
-            match buffer {
-                Ok(buffer) => {
-                    if buffer.len() == 0 {
-                        Err(Box::new(errors::slaves::SlaveFailedError))
-                    } else {
-                        Ok(Response::Buffer(buffer))
-                    }
-                },
-                Err(_) => {
-                    Err(Box::new(errors::slaves::SlaveFailedError))
-                }
+        biased;
+
+        res = pool::send(slave.socket, &slave.token, req) => {
+            match res {
+                Ok(res) => Ok(relayed(res)),
+                Err(e) => Err(retry::slave_error(e)),
             }
         }
 
         _ = slave.token.cancelled() => {
-            Err(Box::new(errors::slaves::SlaveFailedError))
+            Err(Box::new(errors::slaves::SlaveResponseLostError))
         }
     }
 }
 
-async fn add_slave(req: HttpRequest, remote: SocketAddr) -> Response {
+async fn add_slave(req: HttpRequest, ctx: Context) -> Response {
     let slave_code = env::var("SLAVE_CODE").unwrap();
+    let port = *ctx.get::<u16>("port");
 
-    let Some(port) = req.params.get("port") else {
-        return Response::HTTP(invalid_request("Missing port parameter!".to_string()));
-    };
-
-    let Ok(port) = port.parse::<u16>() else {
-        return Response::HTTP(invalid_request("Invalid port parameter!".to_string()));
-    };
-
-    let Some(code) = req.params.get("slave_code") else {
-        return Response::HTTP(invalid_request("Missing code parameter!".to_string()));
-    };
-
-    if slave_code != *code {
+    if slave_code != *ctx.get::<String>("slave_code") {
         return Response::HTTP(invalid_request("Invalid code parameter!".to_string()));
     }
 
-    let ip_socket: SocketAddr = format!("{}:{}", remote.ip(), port).parse().unwrap();
+    let ip_socket: SocketAddr = format!("{}:{}", ctx.remote.ip(), port).parse().unwrap();
 
-    slaves::add(ip_socket).await;
+    // Heartbeats also say how many threads are busy, which older slaves don't
+    let busy = req.param("busy").and_then(|busy| busy.parse::<usize>().ok());
+    slaves::add(ip_socket, busy).await;
 
     Response::HTTP(valid_request("".to_string()))
 }
@@ -7,6 +7,8 @@ use tokio_util::sync::CancellationToken;
 
 use crate::models::slave::Slave;
 
+use super::events::{self, WorkerEvent};
+
 static SLAVES_INDEX: LazyLock<Arc<Mutex<IndexMap<SocketAddr, Slave>>>> = LazyLock::new(|| build_index());
 static CURRENT_SLAVE: LazyLock<Arc<Mutex<usize>>> = LazyLock::new(|| Arc::new(Mutex::new(0)));
 static SLAVES: LazyLock<Arc<Mutex<JoinSet<()>>>> = LazyLock::new(|| build_slaves());
@@ -32,12 +34,14 @@ async fn monitor_slave(slave: Slave) -> bool {
         // If this branch completes, we need to cancel all slave-related tasks
         // and remove it from the map to avoid further assignments
         slave.token.cancel();
-        let _ = remove(slave.socket);
+        remove(slave.socket);
         false
     }
 }
 
-pub async fn add(socket: SocketAddr) {
+// Registers a slave or, if it's known, renews it. Heartbeats from older
+// slaves don't say how many threads are busy
+pub async fn add(socket: SocketAddr, busy: Option<usize>) {
     // We need to first get a lock on the vector
     let slaves_index = Arc::clone(&*SLAVES_INDEX);
     let mut slaves_index = slaves_index.lock();
@@ -46,6 +50,14 @@ pub async fn add(socket: SocketAddr) {
         let is_active = Arc::clone(&slave.is_active);
         let mut is_active = is_active.lock();
         *is_active = true;
+
+        let mut current = slave.busy.lock();
+
+        if let Some(busy) = busy.filter(|busy| *busy != *current) {
+            *current = busy;
+            events::publish(WorkerEvent::Busy { socket, busy });
+        }
+
         return;
     }
 
@@ -54,7 +66,8 @@ pub async fn add(socket: SocketAddr) {
     let token = CancellationToken::new();
 
     let is_active = Arc::new(Mutex::new(true));
-    let slave = Slave { socket, token, is_active };
+    let busy = busy.unwrap_or(0);
+    let slave = Slave { socket, token, is_active, busy: Arc::new(Mutex::new(busy)) };
     let slave_clone = slave.clone();
 
     // We spawn the thread that will continue to monitor the slave
@@ -66,13 +79,17 @@ pub async fn add(socket: SocketAddr) {
     });
 
     slaves_index.insert(socket, slave);
+    events::publish(WorkerEvent::Registered { socket, busy });
 }
 
 pub fn remove(socket: SocketAddr) {
     // We need to first get a lock on the vector
     let slaves_index = Arc::clone(&*SLAVES_INDEX);
     let mut slaves_index = slaves_index.lock();
-    let _ = slaves_index.shift_remove(&socket);
+
+    if slaves_index.shift_remove(&socket).is_some() {
+        events::publish(WorkerEvent::Removed { socket });
+    }
 }
 
 pub fn get_current() -> Option<Slave> {
@@ -114,6 +131,12 @@ pub fn get_specific(index: usize) -> Option<Slave> {
     Some(slave.clone())
 }
 
+pub fn get_all() -> Vec<Slave> {
+    let slaves_index = Arc::clone(&*SLAVES_INDEX);
+    let slaves_index = slaves_index.lock();
+    slaves_index.values().cloned().collect()
+}
+
 pub fn get_quantity() -> usize {
     let slaves_index = Arc::clone(&*SLAVES_INDEX);
     let slaves_index = slaves_index.lock();
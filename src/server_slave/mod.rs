@@ -5,6 +5,7 @@ mod tests {
     use std::collections::HashMap;
     use std::env;
     use std::net::SocketAddr;
+    use crate::models::headers::Headers;
     use crate::models::request;
     use super::*;
 
@@ -52,7 +53,7 @@ mod tests {
         params.insert("content".to_string(), "test".to_string());
         params.insert("repeat".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -71,7 +72,7 @@ mod tests {
         params.insert("content".to_string(), "test".to_string());
         params.insert("repeat".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -90,7 +91,7 @@ mod tests {
         params.insert("name".to_string(), "test".to_string());
         params.insert("repeat".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -109,7 +110,7 @@ mod tests {
         params.insert("name".to_string(), "test".to_string());
         params.insert("content".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -129,7 +130,7 @@ mod tests {
         params.insert("content".to_string(), "test".to_string());
         params.insert("repeat".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -149,7 +150,7 @@ mod tests {
         params.insert("content".to_string(), "test".to_string());
         params.insert("repeat".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -174,7 +175,7 @@ mod tests {
         params.insert("content".to_string(), "test".to_string());
         params.insert("repeat".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -185,6 +186,29 @@ mod tests {
         assert_eq!(res.status, 200);
     }
 
+    #[test]
+    fn createfile_body_success() {
+        let remote = set_variables();
+        let method = "POST".to_string();
+        let uri = vec!["createfile".to_string()];
+        let mut params = HashMap::<String, String>::new();
+        params.insert("name".to_string(), "create_body_route_test".to_string());
+        let version = "1.1".to_string();
+        let headers = Headers::new();
+        let body = request::Body::URLdec(vec![
+            ("content".to_string(), "hola mundo".to_string()),
+            ("repeat".to_string(), "2".to_string()),
+        ]);
+
+        let req = request::HttpRequest::new(method, uri, params, version, headers, body);
+        let res = routes::handle_route(req, remote);
+        let content = std::fs::read_to_string("create_body_route_test");
+        let _ = std::fs::remove_file("create_body_route_test");
+
+        assert_eq!(res.status, 200);
+        assert_eq!(content.unwrap(), "hola mundohola mundo");
+    }
+
     #[test]
     #[should_panic]
     fn deletefile_method_error() {
@@ -194,7 +218,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("name".to_string(), "delete_route_test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -211,7 +235,7 @@ mod tests {
         let uri = vec!["deletefile".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -229,7 +253,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("name".to_string(), "delete_route_test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -249,7 +273,7 @@ mod tests {
         params.insert("content".to_string(), "test".to_string());
         params.insert("repeat".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -260,7 +284,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("name".to_string(), name);
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -278,7 +302,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("num".to_string(), "100".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -295,7 +319,7 @@ mod tests {
         let uri = vec!["fibonacci".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -313,7 +337,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("num".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -331,7 +355,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("num".to_string(), "1000".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -348,7 +372,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("num".to_string(), "100".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -366,7 +390,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("text".to_string(), "hello".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -383,7 +407,7 @@ mod tests {
         let uri = vec!["hash".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -400,7 +424,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("text".to_string(), "hello".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -417,7 +441,7 @@ mod tests {
         let uri = vec!["help".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -433,7 +457,7 @@ mod tests {
         let uri = vec!["help".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -450,7 +474,7 @@ mod tests {
         let uri = vec!["loadtest".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -468,7 +492,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("sleep".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -486,7 +510,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("tasks".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -505,7 +529,7 @@ mod tests {
         params.insert("tasks".to_string(), "test".to_string());
         params.insert("sleep".to_string(), "5".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -524,7 +548,7 @@ mod tests {
         params.insert("tasks".to_string(), "10".to_string());
         params.insert("sleep".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -544,7 +568,7 @@ mod tests {
         params.insert("min".to_string(), "0".to_string());
         params.insert("max".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -563,7 +587,7 @@ mod tests {
         params.insert("min".to_string(), "0".to_string());
         params.insert("max".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -582,7 +606,7 @@ mod tests {
         params.insert("count".to_string(), "10".to_string());
         params.insert("max".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -601,7 +625,7 @@ mod tests {
         params.insert("count".to_string(), "10".to_string());
         params.insert("min".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -621,7 +645,7 @@ mod tests {
         params.insert("min".to_string(), "0".to_string());
         params.insert("max".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -641,7 +665,7 @@ mod tests {
         params.insert("min".to_string(), "test".to_string());
         params.insert("max".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -661,7 +685,7 @@ mod tests {
         params.insert("min".to_string(), "0".to_string());
         params.insert("max".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -680,7 +704,7 @@ mod tests {
         params.insert("min".to_string(), "0".to_string());
         params.insert("max".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -698,7 +722,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("text".to_string(), "hello".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -715,7 +739,7 @@ mod tests {
         let uri = vec!["reverse".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -732,7 +756,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("text".to_string(), "hello".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -751,7 +775,7 @@ mod tests {
         params.insert("tasks".to_string(), "10".to_string());
         params.insert("seconds".to_string(), "1".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -769,7 +793,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("seconds".to_string(), "1".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -787,7 +811,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("task".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -806,7 +830,7 @@ mod tests {
         params.insert("tasks".to_string(), "10".to_string());
         params.insert("seconds".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -825,7 +849,7 @@ mod tests {
         params.insert("tasks".to_string(), "10".to_string());
         params.insert("seconds".to_string(), "1".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -843,7 +867,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("seconds".to_string(), "1".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -860,7 +884,7 @@ mod tests {
         let uri = vec!["sleep".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -878,7 +902,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("seconds".to_string(), "test".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -895,7 +919,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("seconds".to_string(), "2".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -912,7 +936,7 @@ mod tests {
         let uri = vec!["status".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -929,7 +953,7 @@ mod tests {
         let uri = vec!["timestamp".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -945,7 +969,7 @@ mod tests {
         let uri = vec!["timestamp".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -963,7 +987,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("text".to_string(), "hello".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -980,7 +1004,7 @@ mod tests {
         let uri = vec!["toupper".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -997,7 +1021,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("text".to_string(), "hello".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -1017,7 +1041,7 @@ mod tests {
         params.insert("part".to_string(), "0".to_string());
         params.insert("total".to_string(), "10".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
@@ -1036,13 +1060,13 @@ mod tests {
         params.insert("part".to_string(), "0".to_string());
         params.insert("total".to_string(), "3".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(String::new());
 
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
         let res = routes::handle_route(req, remote);
 
-        assert_eq!(res.contents, "file=counttest.txt,part=0,words=10");
+        assert_eq!(res.contents_str(), "file=counttest.txt,part=0,words=10");
     }
 
     #[test]
@@ -1053,7 +1077,7 @@ mod tests {
         let mut params = HashMap::<String, String>::new();
         params.insert("name".to_string(), "counttest.txt".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(r#"
             {
                 "values": [
@@ -1074,7 +1098,7 @@ mod tests {
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
         let res = routes::handle_route(req, remote);
 
-        assert_eq!(res.contents, "file=counttest.txt,total=273");
+        assert_eq!(res.contents_str(), "file=counttest.txt,total=273");
     }
 
     #[test]
@@ -1086,7 +1110,7 @@ mod tests {
         params.insert("row".to_string(), "0".to_string());
         params.insert("column".to_string(), "0".to_string());
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(r#"
             {
                 "matrix_a": {
@@ -1119,7 +1143,7 @@ mod tests {
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
         let res = routes::handle_route(req, remote);
 
-        assert_eq!(res.contents, "row=0, column=0, value=19");
+        assert_eq!(res.contents_str(), "row=0, column=0, value=19");
     }
 
     #[test]
@@ -1129,7 +1153,7 @@ mod tests {
         let uri = vec!["matrixtotal".to_string()];
         let params = HashMap::<String, String>::new();
         let version = "1.1".to_string();
-        let headers = HashMap::<String, String>::new();
+        let headers = Headers::new();
         let body = request::Body::JSON(r#"
             {
                 "values": [
@@ -1160,6 +1184,6 @@ mod tests {
         let req = request::HttpRequest::new(method, uri, params, version, headers, body);
         let res = routes::handle_route(req, remote);
 
-        assert_eq!(res.contents, "{\"matrix\":[[19,22],[43,50]]}");
+        assert_eq!(res.contents_str(), "{\"matrix\":[[19,22],[43,50]]}");
     }
 }
@@ -1,5 +1,5 @@
-use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::LazyLock;
 
 use crate::errors::log_error;
 use crate::models::matrix::MatrixPartialRes;
@@ -7,59 +7,49 @@ use crate::models::{request::HttpRequest, response::HttpResponse};
 use crate::status::status;
 use crate::{distributed, functions};
 use crate::redis_comm;
-
-pub fn handle_route(req: HttpRequest, _: SocketAddr) -> HttpResponse {
-    // Based on parsing logic, the vector will always have at least 1 item
-    let base_uri = req.uri[0].as_str();
-    update_thread_status(true, base_uri.to_string());
-    println!("Route: {}", base_uri);
-    match base_uri {
-        "createfile" => createfile(req),
-        "deletefile" => deletefile(req),
-        "fibonacci" => fibonacci(req),
-        "hash" => hash(req),
-        "random" => random(req),
-        "reverse" => reverse(req),
-        "simulate" => simulate(req),
-        "sleep" => sleep(req),
-        "status" => status(req),
-        "timestamp" => timestamp(req),
-        "toupper" => toupper(req),
-        "countpartial" => count_partial(req),
-        "counttotal" => count_total(req),
-        "matrixpartial" => matrix_partial(req),
-        "matrixtotal" => matrix_total(req),
-        "ping" => ping(req),
-        _ => HttpResponse::basic(404)
-    }
-}
-
-fn ping(_: HttpRequest) -> HttpResponse {
+use crate::server_base::router::{Context, Route, Router};
+
+static ROUTER: LazyLock<Router<HttpResponse>> = LazyLock::new(router);
+
+// Parameters of the distributed routes are also checked by the master, but
+// the slaves can be reached directly
+fn router() -> Router<HttpResponse> {
+    Router::new()
+        .on_dispatch(|req| {
+            update_thread_status(true, req.uri[0].clone());
+            println!("Route: {}", req.uri[0]);
+        })
+        .route(Route::sync("POST", "/createfile", createfile).param::<String>("name").param::<String>("content").param::<u64>("repeat"))
+        .route(Route::sync("DELETE", "/deletefile", deletefile).param::<String>("name"))
+        .route(Route::sync("GET", "/fibonacci", fibonacci).param::<u128>("num"))
+        .route(Route::sync("GET", "/hash", hash).param::<String>("text"))
+        .route(Route::sync("GET", "/random", random).param::<usize>("count").param::<i32>("min").param::<i32>("max"))
+        .route(Route::sync("GET", "/reverse", reverse).param::<String>("text"))
+        .route(Route::sync("GET", "/simulate", simulate).param::<String>("task").param::<u64>("seconds"))
+        .route(Route::sync("GET", "/sleep", sleep).param::<u64>("seconds"))
+        .route(Route::sync("GET", "/status", status))
+        .route(Route::sync("GET", "/timestamp", timestamp))
+        .route(Route::sync("GET", "/toupper", toupper).param::<String>("text"))
+        .route(Route::sync("GET", "/countpartial", count_partial).param::<String>("name").param::<usize>("part").param::<usize>("total").hidden())
+        .route(Route::sync("GET", "/counttotal", count_total).param::<String>("name").hidden())
+        .route(Route::sync("GET", "/matrixpartial", matrix_partial).param::<String>("job").param::<usize>("row").param::<usize>("column").hidden())
+        .route(Route::sync("GET", "/matrixtotal", matrix_total).param::<String>("job").hidden())
+        .route(Route::sync("GET", "/ping", ping).hidden())
+}
+
+pub fn handle_route(req: HttpRequest, remote: SocketAddr) -> HttpResponse {
+    ROUTER.handle_sync(req, remote).unwrap_or_else(|res| res)
+}
+
+fn ping(_: HttpRequest, _: Context) -> HttpResponse {
     valid_request("".to_string()) 
 }
 
-fn createfile(req: HttpRequest) -> HttpResponse {
-    if req.method != "POST" {
-        return HttpResponse::basic(405);
-    }
-
-    let name = req.params.get("name");
-    let content = req.params.get("content");
-    let repeat = req.params.get("repeat");
-
-    if !(name.is_some() && content.is_some() && repeat.is_some()) {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let name = name.unwrap();
-    let content = content.unwrap();
-    let repeat = repeat.unwrap().parse::<u64>();
-
-    if let Err(_) = repeat {
-        return invalid_request("Unable to parse repeat param!".to_string());
-    }
+fn createfile(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let name = ctx.get::<String>("name");
+    let content = ctx.get::<String>("content");
+    let repeat = *ctx.get::<u64>("repeat");
 
-    let repeat = repeat.unwrap();
     match functions::createfile::createfile(name, content, repeat) {
         Ok(_) => HttpResponse::basic(200),
         Err(e) => {
@@ -72,19 +62,8 @@ fn createfile(req: HttpRequest) -> HttpResponse {
     }
 }
 
-fn deletefile(req: HttpRequest) -> HttpResponse {
-    if req.method != "DELETE" {
-        return HttpResponse::basic(405);
-    }
-
-    let name = req.params.get("name");
-
-    if name.is_none() {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let name = name.unwrap();
-    let run = functions::deletefile::deletefile(name);
+fn deletefile(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let run = functions::deletefile::deletefile(ctx.get::<String>("name"));
 
     if let Err(_) = run {
         return invalid_request("Unable to delete file!".to_string());
@@ -93,25 +72,8 @@ fn deletefile(req: HttpRequest) -> HttpResponse {
     HttpResponse::basic(200)
 }
 
-fn fibonacci(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
-    let num = req.params.get("num");
-
-    if num.is_none() {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let num = num.unwrap().parse::<u128>();
-    
-    if let Err(_) = num {
-        return invalid_request("Unable to parse num param!".to_string());
-    }
-
-    let num = num.unwrap();
-    let run = functions::fibonacci::fibonacci(num);
+fn fibonacci(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let run = functions::fibonacci::fibonacci(*ctx.get::<u128>("num"));
     
     if run.is_none() {
         return HttpResponse::basic(507);
@@ -120,54 +82,15 @@ fn fibonacci(req: HttpRequest) -> HttpResponse {
     valid_request(run.unwrap().to_string())
 }
 
-fn hash(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
-    let text = req.params.get("text");
-
-    if text.is_none() {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let text = text.unwrap();
-    let run = functions::hash::hash(text);
+fn hash(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let run = functions::hash::hash(ctx.get::<String>("text"));
     valid_request(run)
 }
 
-fn random(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
-    let count = req.params.get("count");
-    let min = req.params.get("min");
-    let max = req.params.get("max");
-
-    if !(count.is_some() && min.is_some() && max.is_some()) {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let count = count.unwrap().parse::<usize>();
-    let min = min.unwrap().parse::<i32>();
-    let max = max.unwrap().parse::<i32>();
-
-    if let Err(_) = count {
-        return invalid_request("Unable to parse count!".to_string());
-    }
-
-    if let Err(_) = min {
-        return invalid_request("Unable to parse min!".to_string());
-    }
-
-    if let Err(_) = max {
-        return invalid_request("Unable to parse max!".to_string());
-    }
-
-    let count = count.unwrap();
-    let min = min.unwrap();
-    let max = max.unwrap();
+fn random(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let count = *ctx.get::<usize>("count");
+    let min = *ctx.get::<i32>("min");
+    let max = *ctx.get::<i32>("max");
 
     let run = functions::random::random(count, min, max);
 
@@ -177,140 +100,60 @@ fn random(req: HttpRequest) -> HttpResponse {
     }
 }
 
-fn reverse(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
-    let text = req.params.get("text");
-
-    if text.is_none() {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let text = text.unwrap();
-    let run = functions::reverse::reverse(text);
+fn reverse(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let run = functions::reverse::reverse(ctx.get::<String>("text"));
     valid_request(run)
 }
 
-fn simulate(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
+fn simulate(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let task = ctx.get::<String>("task");
+    let seconds = *ctx.get::<u64>("seconds");
 
-    let task = req.params.get("task");
-    let seconds = req.params.get("seconds");
-
-    if !(task.is_some() && seconds.is_some()) {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let task = task.unwrap();
-    let seconds = seconds.unwrap().parse::<u64>();
-
-    if let Err(_) = seconds {
-        return invalid_request("Unable to parse seconds param!".to_string());
-    }
-
-    let seconds = seconds.unwrap();
     let run = functions::simulate::simulate(seconds, task);
     valid_request(run)
 }
 
-fn sleep(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
-    let seconds = req.params.get("seconds");
-
-    if seconds.is_none() {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let seconds = seconds.unwrap().parse::<u64>();
-
-    if let Err(_) = seconds {
-        return invalid_request("Unable to parse seconds param!".to_string());
-    }
-
-    let seconds = seconds.unwrap();
-    let run = functions::sleep::sleep(seconds);
+fn sleep(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let run = functions::sleep::sleep(*ctx.get::<u64>("seconds"));
     valid_request(run)
 }
 
-fn status(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
-    let contents = status::status();
-
-    let content_length = contents.len();
-    let mut headers: HashMap<String, String> = HashMap::new();
-    headers.insert("Content-Length".to_string(), content_length.to_string());
-    headers.insert("Content-Type".to_string(), "application/json".to_string());
-    
-    HttpResponse::new("HTTP/1.1".to_string(), 200, headers, contents)
+fn status(_: HttpRequest, _: Context) -> HttpResponse {
+    HttpResponse::json(200, status::status())
 }
 
-fn timestamp(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
+fn timestamp(_: HttpRequest, _: Context) -> HttpResponse {
     let run = functions::timestamp::timestamp();
     valid_request(run)
 }
 
-fn toupper(req: HttpRequest) -> HttpResponse {
-    if req.method != "GET" {
-        return HttpResponse::basic(405);
-    }
-
-    let text = req.params.get("text");
-
-    if text.is_none() {
-        return invalid_request("Invalid query params provided!".to_string());
-    }
-
-    let text = text.unwrap();
-    let run = functions::toupper::toupper(text);
+fn toupper(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let run = functions::toupper::toupper(ctx.get::<String>("text"));
     valid_request(run)
 }
 
-fn count_partial(req: HttpRequest) -> HttpResponse {
-    // We can simplify slaves' checks as we perform them in the master
-    // - params provided
-    // - parsing of params
-
-    let name = req.params.get("name").unwrap();
-    let part = req.params.get("part").unwrap();
-    let total = req.params.get("total").unwrap();
-    let part_index = part.parse::<usize>().unwrap();
-    let total_parts= total.parse::<usize>().unwrap();
+fn count_partial(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let name = ctx.get::<String>("name");
+    let part_index = *ctx.get::<usize>("part");
+    let total_parts = *ctx.get::<usize>("total");
+    let part = part_index.to_string();
 
     // We keep this check, as the file could've been manipulated by a 3rd party
     // between executions
-    let filepath = format!("archivos/{}", name);
-    let Ok(text) = std::fs::read_to_string(filepath) else {
+    let Some(text) = functions::files::read(name).ok().and_then(|content| String::from_utf8(content).ok()) else {
         return invalid_request("Could not read file".to_string());
     };
     
     let count = distributed::count_partial::count_part_words(text, part_index, total_parts);
     
-    match redis_comm::count_store::add_count_part_res(name, part, count) {
+    match redis_comm::count_store::add_count_part_res(name, &part, count) {
         Ok(_) => valid_request(format!("file={},part={},words={}", name, part, count)),
         Err(e) => redis_down_response(Box::new(e)),
     }
 }
 
-fn count_total(req: HttpRequest) -> HttpResponse {
-    // We can simplify slaves' checks as we perform them in the master
-    // - params provided
-    // - parsing of params
-
-    let name = req.params.get("name").unwrap();
+fn count_total(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let name = ctx.get::<String>("name");
 
     let values = match redis_comm::count_store::get_count_part_res(name) {
         Ok(values) => values,
@@ -318,7 +161,7 @@ fn count_total(req: HttpRequest) -> HttpResponse {
     };
 
     let res = distributed::count_total::count_join(values);
-    if let Err(e) = redis_comm::count_store::remove_count_part_res(&name) {
+    if let Err(e) = redis_comm::count_store::remove_count_part_res(name) {
         log_error(Box::new(e));
     }
 
@@ -329,16 +172,10 @@ fn count_total(req: HttpRequest) -> HttpResponse {
     valid_request(format!("file={},total={}", name, res))
 }
 
-fn matrix_partial(req: HttpRequest) -> HttpResponse {
-    // We can simplify slaves' checks as we perform them in the master
-    // - params provided
-    // - parsing of params
-
-    let job = req.params.get("job").unwrap();
-    let row = req.params.get("row").unwrap();
-    let column = req.params.get("column").unwrap();
-    let row = row.parse::<usize>().unwrap();
-    let column = column.parse::<usize>().unwrap();
+fn matrix_partial(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let job = ctx.get::<String>("job");
+    let row = *ctx.get::<usize>("row");
+    let column = *ctx.get::<usize>("column");
     
     let matrices = match redis_comm::matrix_store::get_matrices_input(job) {
         Ok(matrices) => matrices,
@@ -355,12 +192,8 @@ fn matrix_partial(req: HttpRequest) -> HttpResponse {
     valid_request(format!("row={}, column={}, value={}", row, column, value))
 }
 
-fn matrix_total(req: HttpRequest) -> HttpResponse {
-    // We can simplify slaves' checks as we perform them in the master
-    // - params provided
-    // - parsing of params
-
-    let job = req.params.get("job").unwrap();
+fn matrix_total(_: HttpRequest, ctx: Context) -> HttpResponse {
+    let job = ctx.get::<String>("job");
 
     let matrices = match redis_comm::matrix_store::get_matrices_input(job) {
         Ok(matrices) => matrices,
@@ -384,30 +217,24 @@ fn matrix_total(req: HttpRequest) -> HttpResponse {
     let res = serde_json::to_string(&res).unwrap();
     let _ = redis_comm::matrix_store::remove_job(job);
 
-    let version = "HTTP/1.1".to_string();
-    let status = 200;
-    let mut headers = HashMap::<String, String>::new();
-    headers.insert("Content-Type".to_string(), "application/json".to_string());
-    headers.insert("Content-Length".to_string(), res.len().to_string());
-
-    HttpResponse::new(version, status, headers, res)
+    HttpResponse::json(200, res)
 }
 
 fn invalid_request(contents: String) -> HttpResponse {
     update_thread_status(false, "".to_string());
-    HttpResponse::new("HTTP/1.1".to_string(), 400, HashMap::new(), contents)
+    HttpResponse::text(400, contents)
 }
 
 fn valid_request(contents: String) -> HttpResponse {
     update_thread_status(false, "".to_string());
-    HttpResponse::new("HTTP/1.1".to_string(), 200, HashMap::new(), contents)
+    HttpResponse::text(200, contents)
 }
 
 fn redis_down_response(error: Box<dyn std::error::Error>) -> HttpResponse {
     update_thread_status(false, "".to_string());
     let contents = (&error).to_string();
     log_error(error);
-    HttpResponse::new("HTTP/1.1".to_string(), 500, HashMap::new(), contents)
+    HttpResponse::text(500, contents)
 }
 
 fn update_thread_status(busy: bool, command: String) {
@@ -2,6 +2,7 @@ use std::sync::{Arc, LazyLock};
 use parking_lot::Mutex;
 
 use crate::models::status::Status;
+use crate::server_base::admission;
 
 static STATUS: LazyLock<Arc<Mutex<Status>>> = LazyLock::new(|| build());
 
@@ -35,10 +36,19 @@ pub fn increase_requests_handled() {
     status.increase_requests_handled();
 }
 
+pub fn busy_threads() -> usize {
+    // We need to first get a lock on the vector
+    let status = Arc::clone(&*STATUS);
+    let status = status.lock();
+
+    status.busy_threads()
+}
+
 pub fn status() -> String {
     // We need to first get a lock on the vector
     let status = Arc::clone(&*STATUS);
     let mut status = status.lock();
 
+    status.update_load(admission::load());
     status.status()
 }